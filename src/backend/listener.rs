@@ -1,21 +1,41 @@
 use tokio::sync::{mpsc, RwLock};
+use tokio::time::{self, Duration};
 use tokio_tungstenite::tungstenite::protocol::Message;
 use futures::StreamExt;
 use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 use crate::backend::models::Command;
 use crate::backend::session_state::SessionState;
+use crate::esp32::EmergencyStop;
+use crate::monitoring::health::Task;
+use crate::monitoring::{HEALTH, METRICS};
 
 pub async fn run_listener<R>(
     mut read: R,
     tx: mpsc::Sender<Command>,
     session_state: Arc<RwLock<SessionState>>,
+    estop: EmergencyStop,
 ) 
 where
     R: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin + Send + 'static,
 {
-    println!("[Listener] Online");
+    info!("listener online");
+
+    // Tick the watchdog while idle so a quiet backend is not mistaken for a hang
+    let mut idle = time::interval(Duration::from_secs(1));
+
+    loop {
+        let msg = tokio::select! {
+            msg = read.next() => msg,
+            _ = idle.tick() => {
+                HEALTH.tick(Task::Listener);
+                continue;
+            }
+        };
+        let Some(msg) = msg else { break };
+        HEALTH.tick(Task::Listener);
 
-    while let Some(msg) = read.next().await {
         match msg {
             Ok(Message::Text(text)) => {
                 match serde_json::from_str::<Command>(&text) {
@@ -26,34 +46,54 @@ where
                             Command::StartStream => {
                                 let mut state = session_state.write().await;
                                 state.connected = true;
-                                println!("[Listener] StartStream received, session active");
+                                let session_id = *state.session_id.get_or_insert_with(Uuid::new_v4);
+                                info!(%session_id, "StartStream received, session active");
                             }
                             Command::StopStream => {
                                 let mut state = session_state.write().await;
                                 state.reset();
-                                println!("[Listener] StopStream received, session reset");
+                                info!("StopStream received, session reset");
+                            }
+                            // Straight to the ESP32: queued moves must not delay it.
+                            // The processor reports the fault and rejects queued motion.
+                            Command::EmergencyStop => {
+                                warn!("EmergencyStop received");
+                                session_state.write().await.fault = Some("emergency stop".to_string());
+                                METRICS.emergency_stops.inc();
+                                if let Err(e) = estop.trigger().await {
+                                    error!(error = %e, "failed to send emergency stop to ESP32");
+                                }
                             }
                             // Forward other commands to processor
                             _ => {
+                                debug!(command = ?message, "queueing command");
                                 if let Err(e) = tx.send(message).await {
-                                    eprintln!("[Listener] Processor queue closed: {}", e);
+                                    error!(error = %e, "processor queue closed");
                                     break;
                                 }
+                                METRICS.command_queue_depth.set((tx.max_capacity() - tx.capacity()) as i64);
                             }
                         }
                     }
                     Err(e) => {
-                        eprintln!("[Listener] Failed to parse JSON: {}", e);
+                        warn!(error = %e, "failed to parse command JSON");
+                        // Let the processor report it, it owns the write half
+                        let invalid = Command::Invalid { error: format!("invalid command: {}", e) };
+                        if tx.send(invalid).await.is_err() {
+                            break;
+                        }
                     }
                 }
             }
             Ok(_) => {}
             Err(e) => {
-                eprintln!("[Listener] WebSocket error: {}", e);
+                error!(error = %e, "WebSocket error");
                 break;
             }
         }
     }
 
-    println!("[Listener] Closed");
+    METRICS.ws_connected.set(0);
+    HEALTH.set_backend_connected(false);
+    info!("listener closed");
 }
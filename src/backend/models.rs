@@ -1,13 +1,57 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 use uuid::Uuid;
 
+use crate::esp32::{FirmwareInfo, LinkHealth};
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Command {
     #[serde(rename = "welcome")] 
     Welcome,
-    Move { direction: String },           // e.g., "UP", "DOWN", "LEFT", "RIGHT"
-    Zoom { direction: String },
+    Move {
+        direction: Direction,             // "up", "down", "left", "right" (any case)
+        #[serde(flatten)]
+        params: MoveParams,
+    },
+    Zoom {
+        direction: ZoomDirection,         // "in", "out" (any case)
+        #[serde(flatten)]
+        params: MoveParams,
+    },
+    GoTo {                                // absolute move; omitted axes stay put
+        #[serde(default, deserialize_with = "coordinate")]
+        x: Option<f64>,
+        #[serde(default, deserialize_with = "coordinate")]
+        y: Option<f64>,
+        #[serde(default, deserialize_with = "coordinate")]
+        z: Option<f64>,
+        #[serde(default)]
+        unit: Unit,                       // "steps" (default) or "um"
+    },
+    Home {                                // drive axes to their endstops and zero them
+        #[serde(default)]
+        axes: Option<Vec<Axis>>,          // defaults to z, x, y
+    },
+    JogStart {                            // move continuously until JogStop or a missed keepalive
+        axis: Axis,
+        direction: Sense,                 // "forward" (up/left/zoom in) or "backward"
+        #[serde(default)]
+        speed: Option<u32>,               // steps per second, defaults to JOG_DEFAULT_SPEED
+    },
+    JogKeepalive,                         // keep the current jog alive, within JOG_TIMEOUT_MS
+    JogStop,
+    SetVelocity {                         // joystick: repeat at UI rate, decays to zero when updates stop
+        #[serde(default)]
+        vx: f64,                          // each -1.0..=1.0, a fraction of MOVE_MAX_SPEED
+        #[serde(default)]
+        vy: f64,                          // forward (up/left/zoom in) is positive
+        #[serde(default)]
+        vz: f64,
+    },
+    EmergencyStop,                        // handled by the listener, bypassing the queue
+    ResetFault,                           // clear the fault latched by EmergencyStop
+    GetStatus,                            // reply with a Status message
     Capture,                              // capture a single image
     StartStream,                          // start live streaming
     StopStream,                           // stop live streaming
@@ -15,8 +59,237 @@ pub enum Command {
     #[serde(rename = "heartbeat")] 
     Heartbeat,                            // heartbeat signal
     Shutdown,                             // shutdown device
+    #[serde(skip)]
+    Invalid { error: String },            // unparsable message, reported back by the processor
+}
+
+impl Command {
+    /// Commands that move the stage, refused while a fault is latched.
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Command::Move { .. }
+                | Command::Zoom { .. }
+                | Command::GoTo { .. }
+                | Command::Home { .. }
+                | Command::JogStart { .. }
+                | Command::JogKeepalive
+                | Command::SetVelocity { .. }
+        )
+    }
+
+    /// Variant name, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Command::Welcome => "Welcome",
+            Command::Move { .. } => "Move",
+            Command::Zoom { .. } => "Zoom",
+            Command::GoTo { .. } => "GoTo",
+            Command::Home { .. } => "Home",
+            Command::JogStart { .. } => "JogStart",
+            Command::JogKeepalive => "JogKeepalive",
+            Command::JogStop => "JogStop",
+            Command::SetVelocity { .. } => "SetVelocity",
+            Command::EmergencyStop => "EmergencyStop",
+            Command::ResetFault => "ResetFault",
+            Command::GetStatus => "GetStatus",
+            Command::Capture => "Capture",
+            Command::StartStream => "StartStream",
+            Command::StopStream => "StopStream",
+            Command::SetMicroscope { .. } => "SetMicroscope",
+            Command::Heartbeat => "Heartbeat",
+            Command::Shutdown => "Shutdown",
+            Command::Invalid { .. } => "Invalid",
+        }
+    }
+}
+
+/// Largest `GoTo` coordinate accepted, in either unit: far beyond any stage,
+/// and still well inside `i64` once converted to steps.
+pub const MAX_COORDINATE: f64 = 1e12;
+
+/// A `GoTo` coordinate, refused when it is not a finite number within `MAX_COORDINATE`.
+fn coordinate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let value = Option::<f64>::deserialize(deserializer)?;
+    match value {
+        Some(v) if !(v.is_finite() && v.abs() <= MAX_COORDINATE) => Err(serde::de::Error::custom(format!(
+            "coordinate {} is out of range (at most ±{:e})",
+            v, MAX_COORDINATE
+        ))),
+        _ => Ok(value),
+    }
+}
+
+/// Optional size and speed of a relative `Move` / `Zoom`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoveParams {
+    #[serde(default)]
+    pub steps: Option<u32>,               // defaults to MOVE_DEFAULT_STEPS
+    #[serde(default)]
+    pub distance_um: Option<f64>,         // alternative to steps, converted by axis calibration
+    #[serde(default)]
+    pub speed: Option<u32>,               // steps per second, motor default if unset
+    #[serde(default)]
+    pub accel: Option<u32>,               // steps per second squared, motor default if unset
+}
+
+/// Stage axis. Motor mapping comes from the axis calibration (AXIS_<X|Y|Z>_MOTOR).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 3] = [Axis::X, Axis::Y, Axis::Z];
+}
+
+/// Unit for absolute positions sent by the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Unit {
+    #[default]
+    Steps,
+    Um,
+}
+
+/// Absolute stage position in motor steps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+impl Position {
+    pub fn get(&self, axis: Axis) -> i64 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+
+    pub fn set(&mut self, axis: Axis, value: i64) {
+        match axis {
+            Axis::X => self.x = value,
+            Axis::Y => self.y = value,
+            Axis::Z => self.z = value,
+        }
+    }
+}
+
+/// Stage position in micrometres, as reported to the backend.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PositionUm {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// Sign of a move along an axis, as understood by the ESP32 (`FWD` / `BWD`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Sense {
+    Forward,
+    Backward,
 }
 
+impl Sense {
+    pub fn as_esp(self) -> &'static str {
+        match self {
+            Sense::Forward => "FWD",
+            Sense::Backward => "BWD",
+        }
+    }
+
+    pub fn from_esp(direction: &str) -> Option<Sense> {
+        match direction {
+            "FWD" => Some(Sense::Forward),
+            "BWD" => Some(Sense::Backward),
+            _ => None,
+        }
+    }
+}
+
+/// Stage direction from the UI's arrow buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn axis(self) -> Axis {
+        match self {
+            Direction::Up | Direction::Down => Axis::Y,
+            Direction::Left | Direction::Right => Axis::X,
+        }
+    }
+
+    pub fn sense(self) -> Sense {
+        match self {
+            Direction::Up | Direction::Left => Sense::Forward,
+            Direction::Down | Direction::Right => Sense::Backward,
+        }
+    }
+}
+
+/// Focus direction for `Zoom`, moving the Z axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", try_from = "String")]
+pub enum ZoomDirection {
+    In,
+    Out,
+}
+
+impl ZoomDirection {
+    pub fn sense(self) -> Sense {
+        match self {
+            ZoomDirection::In => Sense::Forward,
+            ZoomDirection::Out => Sense::Backward,
+        }
+    }
+}
+
+/// Case-insensitive parsing from the wire, listing the accepted values on error.
+macro_rules! case_insensitive {
+    ($ty:ident { $($name:literal => $variant:ident),+ $(,)? }) => {
+        impl TryFrom<String> for $ty {
+            type Error = String;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                match value.to_ascii_lowercase().as_str() {
+                    $($name => Ok($ty::$variant),)+
+                    _ => Err(format!(
+                        "invalid {} '{}', expected one of: {}",
+                        stringify!($ty).to_ascii_lowercase(),
+                        value,
+                        [$($name),+].join(", ")
+                    )),
+                }
+            }
+        }
+
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(match self { $($ty::$variant => $name,)+ })
+            }
+        }
+    };
+}
+
+case_insensitive!(Axis { "x" => X, "y" => Y, "z" => Z });
+case_insensitive!(Direction { "up" => Up, "down" => Down, "left" => Left, "right" => Right });
+case_insensitive!(ZoomDirection { "in" => In, "out" => Out });
+case_insensitive!(Sense { "forward" => Forward, "backward" => Backward });
+case_insensitive!(Unit { "steps" => Steps, "um" => Um });
+
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -34,6 +307,73 @@ pub enum Response {
     },
     StreamStarted,
     StreamStopped,
-    Status { status: String },            // e.g., "Idle", "Moving", etc.
+    Status {
+        status: String,                   // e.g., "Idle", "Moving", etc.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<Position>,       // absolute stage position in steps
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position_um: Option<PositionUm>,  // same position in micrometres
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        homed: Option<Vec<Axis>>,         // axes homed since power-up
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        controllers: Vec<ControllerStatus>, // one per ESP32 board
+    },
+    Accepted { command: String },         // the ESP32 started a motion command
+    Completed { command: String },        // the motion finished, a Status follows
+    Failed { command: String, reason: String }, // the motion was refused or stopped early
+    Homed { axes: Vec<Axis> },            // homing finished, these axes are now at zero
+    LimitTriggered {                      // a limit switch stopped an axis
+        axis: Axis,
+        direction: String,                // "FWD" / "BWD" as reported by the ESP32
+    },
+    Stalled { axis: Axis },               // the driver lost steps, position needs re-homing
+    DriverFault { axis: Axis, code: String }, // the driver disabled the motor, fault latched
+    Button { button: u8, pressed: bool }, // front panel button on the microscope
+    SensorReading { sensor: String, value: u32 }, // raw value as sent by the ESP32
     Heartbeat { heartbeat: String },      // e.g., "alive"
-}
\ No newline at end of file
+    Fault { reason: String },             // motion halted until ResetFault
+    StageOffline { controller: String },  // an ESP32 disconnected, motion unavailable
+    StageOnline {                         // the ESP32 is back; its axes must be homed again
+        controller: String,
+        firmware: Option<FirmwareInfo>,
+    },
+}
+
+/// Link state of one ESP32 board, as reported in `Status`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControllerStatus {
+    pub name: String,
+    pub connected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<FirmwareInfo>,   // while connected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<LinkHealth>,         // ping round trip and misses, while connected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn go_to(json: &str) -> Result<Command, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    #[test]
+    fn go_to_accepts_coordinates_within_range() {
+        let cmd = go_to(r#"{"type":"GoTo","x":1e12,"z":-2.5}"#).unwrap();
+        assert!(matches!(cmd, Command::GoTo { x: Some(x), y: None, z: Some(z), .. } if x == 1e12 && z == -2.5));
+    }
+
+    #[test]
+    fn go_to_rejects_coordinates_out_of_range() {
+        for json in [
+            r#"{"type":"GoTo","x":1.1e12}"#,
+            r#"{"type":"GoTo","y":-1e300}"#,
+            // Not valid JSON numbers, refused by the parser itself
+            r#"{"type":"GoTo","z":1e400}"#,
+            r#"{"type":"GoTo","x":NaN}"#,
+        ] {
+            assert!(go_to(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -1,21 +1,151 @@
-use tokio::time::{self, Duration};
+use std::collections::VecDeque;
+use tokio::time::{self, Duration, Instant};
 use tokio::sync::mpsc::Receiver;
 use tokio_tungstenite::tungstenite::Message;
 use serde_json::json;
 use futures::SinkExt;
 use std::sync::Arc;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+use uuid::Uuid;
 
 use tokio::sync::RwLock;
-use crate::backend::models::Command;
+use crate::backend::models::{Axis, Command, ControllerStatus, MoveParams, PositionUm, Response, Sense, Unit};
+use crate::config::MotionConfig;
 use crate::backend::session_state::SessionState;
-use crate::esp32::{EspHandler, EspMessage, SerialHandler};
+use crate::esp32::{Completion, Controllers, EspCommand, EspEvent, EspMessage, EspReply};
+use crate::logging::RateLimit;
+use crate::monitoring::health::Task;
+use crate::monitoring::{HEALTH, METRICS};
+use crate::motion::{LimitCheck, Stage, VelocityDrive};
+
+/// Focus first, so the objective backs away from the slide before the stage moves.
+const HOMING_ORDER: [Axis; 3] = [Axis::Z, Axis::X, Axis::Y];
+
+/// How often queued commands are drained and a running jog is checked.
+const COMMAND_TICK: Duration = Duration::from_millis(50);
+
+/// How often to look for the ESP32 again while it is disconnected.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Most motion commands held back for busy controllers; more are refused
+/// rather than replayed long after they were sent.
+const MAX_DEFERRED: usize = 32;
+
+/// Continuous motion started by `JogStart`, running until `JogStop` or a missed keepalive.
+struct Jog {
+    axis: Axis,
+    sense: Sense,
+    speed: u32,
+    /// Position when the jog started, for estimating travel against the soft limits
+    from: i64,
+    started: Instant,
+    last_keepalive: Instant,
+}
+
+/// Speed and acceleration of a relative move; `None` keeps the motor's default.
+#[derive(Debug, Clone, Copy, Default)]
+struct Profile {
+    speed: Option<u32>,
+    accel: Option<u32>,
+}
+
+/// One axis' relative move, run leg by leg to compensate backlash.
+struct AxisMove {
+    axis: Axis,
+    sense: Sense,
+    steps: u32,
+    /// Steps of the first leg spent taking up slack rather than moving the stage
+    take_up: u32,
+    legs: Vec<(Sense, u32)>,
+    /// Index of the leg the motor is running
+    leg: usize,
+    /// When to stop waiting for the leg's DONE / FAIL
+    deadline: Instant,
+}
+
+/// Axis that could not complete its part of an operation, and why.
+type AxisFailure = (Axis, String);
+
+/// What an operation does once nothing of it is running.
+enum Plan {
+    /// Nothing more: the relative moves started with it are the whole operation
+    Move,
+    /// Move towards the absolute targets chunk by chunk
+    GoTo([(Axis, Option<i64>); 3]),
+    /// Home `axes` in turn; `next` is the first not started yet
+    Home { axes: Vec<Axis>, next: usize },
+}
+
+/// A Move, Zoom, GoTo or Home the ESP32s are carrying out. It advances on
+/// their DONE / FAIL events, so the command loop keeps running meanwhile.
+struct Operation {
+    /// Backend command named in the accepted / completed / failed responses
+    command: String,
+    profile: Profile,
+    plan: Plan,
+    running: Vec<AxisMove>,
+    /// Axis driving to its endstop, and when to stop waiting for it
+    homing: Option<(Axis, Instant)>,
+    failures: Vec<AxisFailure>,
+    accepted: bool,
+    /// Soft limit that shortened the move, reported on completion
+    clamped: Option<String>,
+}
+
+impl Operation {
+    fn new(command: &str, profile: Profile, plan: Plan) -> Self {
+        Self {
+            command: command.to_string(),
+            profile,
+            plan,
+            running: Vec::new(),
+            homing: None,
+            failures: Vec::new(),
+            accepted: false,
+            clamped: None,
+        }
+    }
+
+    /// Axes the operation drives or still will; their controllers are its own until it ends.
+    fn axes(&self) -> Vec<Axis> {
+        let mut axes: Vec<Axis> = self.running.iter().map(|m| m.axis).chain(self.homing.map(|(axis, _)| axis)).collect();
+        match &self.plan {
+            Plan::Move => {}
+            Plan::GoTo(targets) => axes.extend(targets.iter().filter_map(|&(axis, target)| target.map(|_| axis))),
+            Plan::Home { axes: homing, next } => axes.extend(&homing[*next..]),
+        }
+        axes
+    }
+}
 
 pub struct Processor<S> {
     rx: Receiver<Command>,
     write: S,
     session_state: Arc<RwLock<SessionState>>,
     latest_frame: Arc<RwLock<Vec<u8>>>, 
-    esp: EspHandler, 
+    esp: Controllers,
+    stage: Stage,
+    motion: MotionConfig,
+    /// Sense each axis' motor last turned in, for backlash compensation
+    last_sense: [Option<Sense>; 3],
+    jog: Option<Jog>,
+    drive: Option<VelocityDrive>,
+    /// Running operations, at most one per controller
+    operations: Vec<Operation>,
+    /// Motion commands held back until their controllers are free, oldest first
+    deferred: VecDeque<Command>,
+    /// A latched fault has been acted on (motion state dropped, backend told)
+    fault_handled: bool,
+    /// Each controller's link as last reported to the backend
+    online: Vec<bool>,
+    /// The fault was reset while the controller was offline, so it still needs RESET
+    reset_pending: Vec<bool>,
+    /// Each controller has been sent the axes' default speed and acceleration
+    /// since it last connected
+    defaults_sent: Vec<bool>,
+    last_reconnect: Instant,
+    frame_log: RateLimit,
+    link_log: RateLimit,
 }
 
 impl<S> Processor<S>
@@ -26,30 +156,54 @@ where
         rx: Receiver<Command>, 
         write: S, 
         session_state: Arc<RwLock<SessionState>>, 
-        latest_frame: Arc<RwLock<Vec<u8>>> ) 
-        
-        -> Self { 
-            let port_name = "/dev/ttyUSB0";
-            let baud_rate = 115200;
-            let serial = SerialHandler::new(port_name, baud_rate)
-                .expect("Failed to open serial port");
-            let esp = EspHandler::new(serial);
-        Self { rx, write, session_state, latest_frame, esp}
+        latest_frame: Arc<RwLock<Vec<u8>>>,
+        esp: Controllers,
+        stage: Stage,
+        motion: MotionConfig )
+
+        -> Self {
+        let online = vec![true; esp.len()];
+        let defaults_sent = vec![false; esp.len()];
+        let reset_pending = vec![false; esp.len()];
+        Self {
+            rx,
+            write,
+            session_state,
+            latest_frame,
+            esp,
+            stage,
+            motion,
+            last_sense: [None; 3],
+            jog: None,
+            drive: None,
+            operations: Vec::new(),
+            deferred: VecDeque::new(),
+            fault_handled: false,
+            // Assume online so an ESP32 missing at startup is reported on the first tick
+            online,
+            reset_pending,
+            defaults_sent,
+            last_reconnect: Instant::now(),
+            frame_log: RateLimit::new(Duration::from_secs(5)),
+            link_log: RateLimit::new(Duration::from_secs(60)),
+        }
     }
 
     pub async fn run(&mut self) {
 
         let mut image_interval = time::interval(Duration::from_millis(17));
-        let mut command_interval = time::interval(Duration::from_millis(50));
+        let mut command_interval = time::interval(COMMAND_TICK);
 
         loop {
+            HEALTH.tick(Task::Processor);
             let connected = {
                 let state = self.session_state.read().await;
-                if !state.connected && !self.rx.is_empty() {
+                if !state.connected && (!self.rx.is_empty() || !self.deferred.is_empty()) {
                     while let Ok(_) = self.rx.try_recv() {
                         // discard messages silently
                     }                    
-                    println!("[Processor] Queue cleared... waiting for new session...");
+                    self.deferred.clear();
+                    info!("queue cleared, waiting for new session");
                     continue;
                 }
                 state.connected
@@ -58,10 +212,7 @@ where
             tokio::select! {
                 //  1 Handle queued commands
                 _ = command_interval.tick() => {
-                    while let Ok(msg) = self.rx.try_recv() {
-                        println!("[Processor] Processing command: {:?}", msg);
-                        self.handle_command(msg).await;
-                    }
+                    self.command_tick().await;
                 }
 
                 //  2 Send images at set FPS
@@ -72,45 +223,231 @@ where
         }
     }
 
+    /// Check the links and running motion, then drain the queued commands.
+    async fn command_tick(&mut self) {
+        self.check_link().await;
+        self.check_fault().await;
+        // Events now arrive without a request in flight (e.g. a jog stopped by its dead-man timer)
+        self.handle_esp_events().await;
+        self.check_operations().await;
+        self.run_deferred().await;
+        let fault = self.session_state.read().await.fault.clone();
+        let mut dropped = 0;
+        while let Ok(msg) = self.rx.try_recv() {
+            METRICS.command_queue_depth.set(self.rx.len() as i64);
+            METRICS.commands.with_label_values(&[msg.kind()]).inc();
+            if fault.is_some() && msg.is_motion() {
+                dropped += 1;
+                continue;
+            }
+            // Motion waits its turn on its controllers; everything else is answered right away
+            if msg.is_motion() && self.must_wait(&msg, self.deferred.len()) {
+                self.defer(msg).await;
+                continue;
+            }
+            self.process(msg).await;
+        }
+        if let Some(reason) = fault.filter(|_| dropped > 0) {
+            // One answer for the whole batch rather than an error per command
+            warn!(dropped, %reason, "fault latched, dropped motion commands");
+            self.send_response(&Response::Fault { reason }).await;
+        }
+        self.check_jog().await;
+        self.drive_velocity().await;
+    }
+
+    async fn process(&mut self, msg: Command) {
+        let span = self.command_span().await;
+        async {
+            debug!(command = ?msg, "processing command");
+            self.handle_command(msg).await;
+        }
+        .instrument(span)
+        .await;
+    }
+
+    /// Whether a motion command has to wait because a running operation, or one
+    /// of the first `earlier` held-back commands, needs one of its controllers.
+    fn must_wait(&self, cmd: &Command, earlier: usize) -> bool {
+        let cal = &self.motion.calibration;
+        let controllers: Vec<usize> = self.command_axes(cmd).into_iter().map(|axis| cal.controller(axis)).collect();
+        self.operations
+            .iter()
+            .flat_map(Operation::axes)
+            .chain(self.deferred.iter().take(earlier).flat_map(|held| self.command_axes(held)))
+            .any(|axis| controllers.contains(&cal.controller(axis)))
+    }
+
+    /// Hold a motion command back until its controllers are free.
+    async fn defer(&mut self, msg: Command) {
+        match msg {
+            Command::JogKeepalive => {
+                // Only refreshes a running jog; one still waiting to start needs none
+                debug!("dropping keepalive held behind other motion");
+                return;
+            }
+            Command::SetVelocity { .. } => {
+                // Only the latest joystick vector matters once the stage is free
+                if let Some(held) = self.deferred.iter_mut().find(|held| matches!(held, Command::SetVelocity { .. })) {
+                    *held = msg;
+                    return;
+                }
+            }
+            _ => {}
+        }
+        if self.deferred.len() >= MAX_DEFERRED {
+            warn!(command = msg.kind(), held = self.deferred.len(), "too many motion commands waiting, dropping command");
+            self.send_error(&format!("{}: too many motion commands waiting, dropped", msg.kind())).await;
+            return;
+        }
+        debug!(command = msg.kind(), "motion in progress, deferring command");
+        self.deferred.push_back(msg);
+    }
+
+    /// Start the held-back commands whose controllers have come free, keeping
+    /// their order on each controller.
+    async fn run_deferred(&mut self) {
+        let mut i = 0;
+        while i < self.deferred.len() {
+            if self.must_wait(&self.deferred[i], i) {
+                i += 1;
+            } else if let Some(msg) = self.deferred.remove(i) {
+                self.process(msg).await;
+            }
+        }
+    }
+
+    /// Span tagging everything logged while handling one command.
+    async fn command_span(&self) -> tracing::Span {
+        let state = self.session_state.read().await;
+        info_span!(
+            "command",
+            request_id = %Uuid::new_v4(),
+            session_id = ?state.session_id,
+            microscope_id = ?state.microscope_id,
+        )
+    }
+
    async fn handle_command(&mut self, cmd: Command) {
+        let fault = self.session_state.read().await.fault.clone();
+        if cmd.is_motion() {
+            if let Some(reason) = fault {
+                // Latched since this tick's queue was drained: check_fault reports it on the next tick
+                warn!(command = cmd.kind(), %reason, "fault latched, dropping motion command");
+                return;
+            }
+            // A board that only drives other axes (or none) does not hold this command up
+            let axes = self.command_axes(&cmd);
+            if !axes.iter().all(|&axis| self.online[self.motion.calibration.controller(axis)]) {
+                warn!(command = cmd.kind(), "ESP32 offline, dropping motion command");
+                if !matches!(cmd, Command::JogKeepalive | Command::SetVelocity { .. }) {
+                    self.send_error(&format!("{}: stage offline", cmd.kind())).await;
+                }
+                return;
+            }
+        }
+
+        let moves_stage = matches!(
+            cmd,
+            Command::Move { .. } | Command::Zoom { .. } | Command::GoTo { .. } | Command::Home { .. }
+        );
+        if moves_stage && self.jog.is_some() {
+            info!(command = cmd.kind(), "stopping jog before motion command");
+            self.stop_jog().await;
+        }
+        if (moves_stage || matches!(cmd, Command::JogStart { .. })) && self.drive.is_some() {
+            info!(command = cmd.kind(), "stopping velocity control before motion command");
+            self.stop_velocity().await;
+        }
+
         match cmd {
             Command::Welcome => {
-                println!("[Processor] Handling Welcome");
+                info!("handling Welcome");
                 self.send_ack("Welcome").await;
             }
             Command::Heartbeat => {
-                println!("[Processor] Handling Heartbeat");
+                debug!("handling Heartbeat");
                 self.send_heartbeat().await;
             }
-            Command::Move { direction } => {
-                println!("[Processor] Moving {}", direction);
-               match direction.as_str() {
-                    "up" => self.send_esp_command("MOVE", 1, "FWD", 5).await,
-                    "down" => self.send_esp_command("MOVE", 1, "BWD", 5).await,
-                    "left" => self.send_esp_command("MOVE", 2, "FWD", 5).await,
-                    "right" => self.send_esp_command("MOVE", 2, "BWD", 5).await,
-                    _ => println!("[Processor] Unknown direction: {}", direction),
-                }
-                self.send_ack(&format!("Move {}", direction)).await;
+            Command::Move { direction, params } => {
+                info!(%direction, ?params, "moving");
+                let label = format!("Move {}", direction);
+                self.move_axis(&label, direction.axis(), direction.sense(), params).await;
+            }
+            Command::Zoom { direction, params } => {
+                info!(%direction, ?params, "zoom");
+                self.move_axis("Zoom", Axis::Z, direction.sense(), params).await;
+            }
+            Command::GoTo { x, y, z, unit } => {
+                info!(?x, ?y, ?z, ?unit, "go to");
+                let to_steps = |axis, value: Option<f64>| {
+                    value.map(|v| match unit {
+                        Unit::Steps => v.round() as i64,
+                        Unit::Um => self.motion.calibration.um_to_steps(axis, v),
+                    })
+                };
+                let targets = [
+                    (Axis::X, to_steps(Axis::X, x)),
+                    (Axis::Y, to_steps(Axis::Y, y)),
+                    (Axis::Z, to_steps(Axis::Z, z)),
+                ];
+                self.go_to(targets).await;
+            }
+            Command::Home { axes } => {
+                let axes = axes.unwrap_or_else(|| HOMING_ORDER.to_vec());
+                info!(?axes, "homing");
+                // One axis after the other, even across controllers, so the
+                // objective is clear before the stage moves
+                self.start_operation(Operation::new("Home", Profile::default(), Plan::Home { axes, next: 0 }), &[]).await;
             }
-            Command::Zoom { direction } => {
-                println!("[Processor] Zoom {}", direction);
-                // if statements for up or down
-                if direction == "in" {
-                    self.send_esp_command("MOVE", 3, "FWD", 5).await;
-                } else if direction == "out" {
-                    self.send_esp_command("MOVE", 3, "BWD", 5).await;
+            Command::JogStart { axis, direction, speed } => {
+                info!(%axis, %direction, ?speed, "jog start");
+                self.start_jog(axis, direction, speed).await;
+            }
+            Command::JogKeepalive => {
+                self.keep_jog_alive().await;
+            }
+            Command::JogStop => {
+                info!("jog stop");
+                // Also cancels a jog still held back for a busy controller
+                let held = self.deferred.len();
+                self.deferred.retain(|cmd| !matches!(cmd, Command::JogStart { .. } | Command::JogKeepalive));
+                if self.deferred.len() < held {
+                    info!("cancelled jog waiting to start");
+                }
+                if self.stop_jog().await {
+                    self.send_ack("JogStop").await;
+                    self.send_status("Idle").await;
+                } else {
+                    self.send_error("JogStop: ESP32 did not confirm the stop, position lost").await;
                 }
-                // send message to associate esp32 motorid
-                self.send_ack(&format!("Zoom")).await;
+            }
+            Command::SetVelocity { vx, vy, vz } => {
+                debug!(vx, vy, vz, "set velocity");
+                self.set_velocity([vx, vy, vz]).await;
+            }
+            Command::ResetFault => {
+                info!("reset fault");
+                self.reset_fault().await;
+            }
+            Command::GetStatus => {
+                let status = match (self.fault_handled, &self.jog, &self.drive) {
+                    _ if !self.stage_online() => "Offline",
+                    (true, _, _) => "Fault",
+                    (false, Some(_), _) => "Jogging",
+                    (false, None, Some(_)) => "Moving",
+                    (false, None, None) if !self.operations.is_empty() => "Moving",
+                    (false, None, None) => "Idle",
+                };
+                self.send_status(status).await;
             }
             Command::Capture => {
-                println!("[Processor] Capturing image");
+                info!("capturing image");
                 self.send_image_frame().await;
                 self.send_ack("Capture").await;
             }
             Command::SetMicroscope { microscope_id } => {
-                println!("[Processor] Set microscope: {}", microscope_id);
+                info!(%microscope_id, "set microscope");
                 {
                     let mut state = self.session_state.write().await;
                     state.microscope_id = Some(microscope_id);
@@ -118,7 +455,7 @@ where
                 self.send_ack(&format!("SetMicroscope {}", microscope_id)).await;
             }
             Command::Shutdown => {
-                println!("[Processor] Shutdown command received");
+                warn!("shutdown command received");
                 self.send_ack("Shutdown").await;
                 
                 let mut state = self.session_state.write().await;
@@ -126,10 +463,916 @@ where
                 state.connected = false;
                 
             }
+            Command::Invalid { error } => {
+                warn!(%error, "rejecting invalid command");
+                self.send_error(&error).await;
+            }
             _ => {
-                println!("[Processor] INVALID COMMAND");
+                warn!(command = ?cmd, "invalid command for processor");
+            }
+        }
+    }
+
+    /// Validate a relative move against the configured limits and start it on the ESP32.
+    async fn move_axis(&mut self, label: &str, axis: Axis, sense: Sense, params: MoveParams) {
+        let steps = match self.validate_motion(axis, &params) {
+            Ok(steps) => steps,
+            Err(message) => {
+                warn!(%axis, %message, "rejecting move");
+                self.send_error(&format!("{}: {}", label, message)).await;
+                return;
+            }
+        };
+
+        let from = self.stage.position().get(axis);
+        let (steps, clamped) = match self.motion.limits.check_move(axis, from, sense, steps) {
+            LimitCheck::Allowed => (steps, None),
+            LimitCheck::Clamped { steps, reason } => (steps, Some(reason)),
+            LimitCheck::Rejected(reason) => {
+                warn!(%axis, %reason, "move rejected by soft limit");
+                self.send_error(&format!("{}: {}", label, reason)).await;
+                return;
+            }
+        };
+
+        let profile = Profile { speed: params.speed, accel: params.accel };
+        let mut operation = Operation::new(label, profile, Plan::Move);
+        operation.clamped = clamped;
+        self.start_operation(operation, &[(axis, sense, steps)]).await;
+    }
+
+    /// Move to an absolute position in chunks of at most `max_steps`.
+    /// Every requested axis must have been homed since power-up.
+    async fn go_to(&mut self, targets: [(Axis, Option<i64>); 3]) {
+        let unhomed: Vec<String> = targets
+            .iter()
+            .filter(|(axis, target)| target.is_some() && !self.stage.is_homed(*axis))
+            .map(|(axis, _)| axis.to_string())
+            .collect();
+        if !unhomed.is_empty() {
+            warn!(?unhomed, "refusing GoTo on unhomed axes");
+            self.send_error(&format!(
+                "GoTo: axis {} not homed since power-up, send Home first",
+                unhomed.join(", ")
+            )).await;
+            return;
+        }
+
+        // Check every axis up front so a rejected target never leaves the stage half-moved
+        for (axis, target) in targets {
+            let Some(target) = target else { continue };
+            if let Err(reason) = self.motion.limits.check_target(axis, target) {
+                warn!(%axis, %reason, "GoTo rejected by soft limit");
+                self.send_error(&format!("GoTo: {}", reason)).await;
+                return;
+            }
+        }
+
+        self.start_operation(Operation::new("GoTo", Profile::default(), Plan::GoTo(targets)), &[]).await;
+    }
+
+    /// Next chunk of a GoTo: one per controller at a time, so axes on different
+    /// boards move together and axes sharing a board finish one after the other.
+    ///
+    /// Fails on an axis whose distance to its target does not fit in `i64`.
+    fn go_to_chunk(&self, targets: [(Axis, Option<i64>); 3]) -> Result<Vec<(Axis, Sense, u32)>, AxisFailure> {
+        let mut chunk: Vec<(Axis, Sense, u32)> = Vec::new();
+        for (axis, target) in targets {
+            let Some(target) = target else { continue };
+            let Some(remaining) = target.checked_sub(self.stage.position().get(axis)) else {
+                return Err((axis, format!("target {} is out of range", target)));
+            };
+            let controller = self.motion.calibration.controller(axis);
+            if remaining == 0 || chunk.iter().any(|&(a, ..)| self.motion.calibration.controller(a) == controller) {
+                continue;
+            }
+            let sense = if remaining > 0 { Sense::Forward } else { Sense::Backward };
+            let steps = remaining.unsigned_abs().min(self.motion.max_steps as u64) as u32;
+            chunk.push((axis, sense, steps));
+        }
+        Ok(chunk)
+    }
+
+    /// Start an operation with `moves`; the command loop carries it on from
+    /// the ESP32s' DONE / FAIL events.
+    async fn start_operation(&mut self, operation: Operation, moves: &[(Axis, Sense, u32)]) {
+        self.operations.push(operation);
+        let op = self.operations.len() - 1;
+        for &(axis, sense, steps) in moves {
+            self.start_axis_move(op, axis, sense, steps).await;
+        }
+        self.advance_operation(op).await;
+    }
+
+    /// Once nothing of operation `op` is running, start its next step or
+    /// report it finished.
+    ///
+    /// Operations are indexed by position, which only shifts when one finishes
+    /// here, at the end of whatever started or advanced it.
+    async fn advance_operation(&mut self, op: usize) {
+        loop {
+            let operation = &self.operations[op];
+            if !operation.running.is_empty() || operation.homing.is_some() {
+                return;
             }
+            if !operation.failures.is_empty() {
+                break;
+            }
+            match &operation.plan {
+                Plan::Move => break,
+                Plan::GoTo(targets) => {
+                    let chunk = match self.go_to_chunk(*targets) {
+                        Ok(chunk) => chunk,
+                        Err(failure) => {
+                            self.operations[op].failures.push(failure);
+                            break;
+                        }
+                    };
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    for (axis, sense, steps) in chunk {
+                        self.start_axis_move(op, axis, sense, steps).await;
+                    }
+                }
+                Plan::Home { axes, next } => {
+                    let Some(&axis) = axes.get(*next) else { break };
+                    self.start_home(op, axis).await;
+                }
+            }
+        }
+        let operation = self.operations.remove(op);
+        self.finish_operation(operation).await;
+    }
+
+    async fn finish_operation(&mut self, operation: Operation) {
+        let command = operation.command;
+        if operation.failures.is_empty() {
+            if !operation.accepted {
+                // Already there: nothing was sent, but the backend still gets both responses
+                self.send_response(&Response::Accepted { command: command.clone() }).await;
+            }
+            if let Some(reason) = operation.clamped {
+                warn!(command, %reason, "move clamped by soft limit");
+                self.send_error(&format!("{}: clamped, {}", command, reason)).await;
+            }
+            if let Plan::Home { axes, .. } = operation.plan {
+                self.send_response(&Response::Homed { axes }).await;
+            }
+            self.send_response(&Response::Completed { command }).await;
+        } else {
+            let failures: Vec<String> = operation
+                .failures
+                .iter()
+                .map(|(axis, reason)| format!("{} axis: {}", axis, reason))
+                .collect();
+            let reason = match operation.plan {
+                Plan::Move => operation.failures[0].1.clone(),
+                Plan::GoTo(_) => format!("{}, stopped at {:?}", failures.join("; "), self.stage.position()),
+                Plan::Home { .. } => failures.join("; "),
+            };
+            warn!(command, %reason, "motion failed");
+            self.send_response(&Response::Failed { command, reason }).await;
+        }
+        self.send_status(if self.operations.is_empty() { "Idle" } else { "Moving" }).await;
+    }
+
+    /// Start one relative move of operation `op`, compensating backlash.
+    async fn start_axis_move(&mut self, op: usize, axis: Axis, sense: Sense, steps: u32) {
+        let last = self.last_sense[axis as usize];
+        // The stage goes one backlash past the target before coming back to it
+        let from = self.stage.position().get(axis);
+        let overshoot = steps.saturating_add(self.motion.backlash.steps(axis));
+        let room = matches!(self.motion.limits.check_move(axis, from, sense, overshoot), LimitCheck::Allowed);
+        let legs = self.motion.backlash.plan(axis, last, sense, steps, room);
+        if legs.len() > 1 || legs[0].1 != steps {
+            debug!(%axis, moves = ?legs, "compensating backlash");
+        }
+        let take_up = self.motion.backlash.take_up(axis, last, sense);
+        let profile = self.operations[op].profile;
+
+        let started = self.send_leg(axis, legs[0], profile).await;
+        let deadline = Instant::now() + self.motion.move_timeout;
+        let operation = &mut self.operations[op];
+        match started {
+            Ok(()) => {
+                operation.running.push(AxisMove { axis, sense, steps, take_up, legs, leg: 0, deadline });
+                self.accept_operation(op).await;
+            }
+            Err(reason) => operation.failures.push((axis, reason)),
+        }
+    }
+
+    /// Send HOME for the next axis of operation `op`; the ESP32 reports DONE
+    /// once the axis is at its endstop.
+    async fn start_home(&mut self, op: usize, axis: Axis) {
+        let started = if self.faulted().await {
+            Err("fault latched".to_string())
+        } else {
+            let (controller, motor) = self.motion.calibration.route(axis);
+            let esp_sense = self.motion.calibration.esp_sense(axis, Sense::Backward);
+            match self.send_esp_command(controller, EspCommand::Home, motor, esp_sense.as_esp(), 0).await {
+                Some(EspReply::Ack) => Ok(()),
+                Some(EspReply::Err) => Err("ESP32 rejected homing".to_string()),
+                None => Err("no reply from ESP32".to_string()),
+            }
+        };
+        let deadline = Instant::now() + self.motion.homing_timeout;
+        let operation = &mut self.operations[op];
+        if let Plan::Home { next, .. } = &mut operation.plan {
+            *next += 1;
+        }
+        match started {
+            Ok(()) => {
+                operation.homing = Some((axis, deadline));
+                self.accept_operation(op).await;
+            }
+            Err(reason) => {
+                error!(%axis, %reason, "homing failed");
+                self.stage.invalidate(axis);
+                operation.failures.push((axis, reason));
+            }
+        }
+    }
+
+    /// Tell the backend operation `op` started, once.
+    async fn accept_operation(&mut self, op: usize) {
+        let operation = &mut self.operations[op];
+        if !operation.accepted {
+            operation.accepted = true;
+            let command = operation.command.clone();
+            self.send_response(&Response::Accepted { command }).await;
+        }
+    }
+
+    /// Send one leg of a relative move; `Ok` once the ESP32 has started it.
+    async fn send_leg(&mut self, axis: Axis, (sense, steps): (Sense, u32), profile: Profile) -> Result<(), String> {
+        if self.faulted().await {
+            return Err("fault latched".to_string());
+        }
+        let (controller, motor) = self.motion.calibration.route(axis);
+        // Older firmware would run the move at its fixed speed rather than refuse it
+        let custom = profile.speed.is_some() || profile.accel.is_some();
+        if custom && !self.esp.get(controller).supports(EspCommand::Set) {
+            return Err("ESP32 firmware does not support speed or acceleration".to_string());
+        }
+        let esp_sense = self.motion.calibration.esp_sense(axis, sense);
+        let mut msg = EspMessage::new(EspCommand::Move, motor, esp_sense.as_esp(), steps)
+            .expect("FWD / BWD is a valid direction");
+        (msg.speed, msg.accel) = (profile.speed, profile.accel);
+        match self.send_esp_message(controller, msg).await {
+            Some(EspReply::Ack) => Ok(()),
+            Some(EspReply::Err) => Err("rejected by ESP32".to_string()),
+            None => {
+                // Unknown whether the motor started, so the slack could be on either side
+                self.last_sense[axis as usize] = None;
+                Err("no reply from ESP32".to_string())
+            }
+        }
+    }
+
+    /// Apply how the running leg on `axis` ended and start its next leg, if any.
+    /// `Err` means the ESP32 never reported, so the motor's position is unknown.
+    async fn finish_leg(&mut self, axis: Axis, completion: Result<Completion, String>) {
+        // An axis is in at most one operation, as operations never share a controller
+        let found = self.operations.iter().enumerate().find_map(|(op, operation)| {
+            if operation.homing.is_some_and(|(homing, _)| homing == axis) {
+                return Some((op, None));
+            }
+            operation.running.iter().position(|m| m.axis == axis).map(|i| (op, Some(i)))
+        });
+        let Some((op, running)) = found else {
+            // The move already gave up on this completion
+            debug!(%axis, ?completion, "completion without a running move");
+            return;
+        };
+        let Some(i) = running else {
+            self.operations[op].homing = None;
+            self.finish_home(op, axis, completion);
+            self.advance_operation(op).await;
+            return;
+        };
+        let mut axis_move = self.operations[op].running.remove(i);
+        let (leg_sense, leg_steps) = axis_move.legs[axis_move.leg];
+        let first = axis_move.leg == 0;
+
+        // Only the first leg carries the stage; later ones just settle the slack
+        let failure = match completion {
+            Ok(Completion::Done { .. }) => {
+                self.last_sense[axis as usize] = Some(leg_sense);
+                if first {
+                    self.stage.apply_move(axis, axis_move.sense, axis_move.steps);
+                }
+                None
+            }
+            Ok(Completion::Failed { reason, steps: moved }) => {
+                if moved > 0 {
+                    self.last_sense[axis as usize] = Some(leg_sense);
+                }
+                let moved_stage = moved.saturating_sub(axis_move.take_up);
+                if first {
+                    self.stage.apply_move(axis, axis_move.sense, moved_stage);
+                }
+                // Stopped past the target in the overshoot, or on the way back from
+                // it: the stage is somewhere the tracked position cannot vouch for
+                if !first || moved_stage > axis_move.steps {
+                    self.stage.invalidate(axis);
+                }
+                Some(format!("stopped by ESP32 ({}) after {} of {} steps", reason, moved, leg_steps))
+            }
+            Err(reason) => {
+                error!(%axis, %reason, "move did not complete, position lost");
+                self.last_sense[axis as usize] = None;
+                self.stage.invalidate(axis);
+                Some(reason)
+            }
+        };
+
+        let failure = match failure {
+            None if axis_move.leg + 1 < axis_move.legs.len() => {
+                axis_move.leg += 1;
+                let profile = self.operations[op].profile;
+                match self.send_leg(axis, axis_move.legs[axis_move.leg], profile).await {
+                    Ok(()) => {
+                        axis_move.deadline = Instant::now() + self.motion.move_timeout;
+                        self.operations[op].running.push(axis_move);
+                        return;
+                    }
+                    Err(reason) => {
+                        // The whole move was applied after the first leg, but the stage is not back on target
+                        self.stage.invalidate(axis);
+                        Some(reason)
+                    }
+                }
+            }
+            failure => failure,
+        };
+        if let Some(reason) = failure {
+            self.operations[op].failures.push((axis, reason));
         }
+        self.advance_operation(op).await;
+    }
+
+    /// Zero an axis that reached its endstop, or record why it did not.
+    fn finish_home(&mut self, op: usize, axis: Axis, completion: Result<Completion, String>) {
+        let reason = match completion {
+            // The endstop's own LIMIT event came before DONE, so zeroing wins over its invalidation
+            Ok(Completion::Done { .. }) => {
+                info!(%axis, "axis homed");
+                self.stage.set_home(axis);
+                self.last_sense[axis as usize] = Some(Sense::Backward);
+                return;
+            }
+            Ok(Completion::Failed { reason, .. }) => format!("stopped by ESP32 ({})", reason),
+            Err(reason) => reason,
+        };
+        error!(%axis, %reason, "homing failed");
+        self.stage.invalidate(axis);
+        self.operations[op].failures.push((axis, reason));
+    }
+
+    /// Give up on legs and homing whose DONE / FAIL is overdue.
+    async fn check_operations(&mut self) {
+        let now = Instant::now();
+        let overdue: Vec<Axis> = self
+            .operations
+            .iter()
+            .flat_map(|operation| operation.running.iter().map(|m| (m.axis, m.deadline)).chain(operation.homing))
+            .filter(|&(_, deadline)| deadline <= now)
+            .map(|(axis, _)| axis)
+            .collect();
+        for axis in overdue {
+            METRICS.esp_timeouts.inc();
+            warn!(%axis, "no completion from ESP32");
+            self.finish_leg(axis, Err("ESP32 did not report completion".to_string())).await;
+        }
+    }
+
+    /// Act on unsolicited ESP32 messages collected during the last exchange.
+    async fn handle_esp_events(&mut self) {
+        for (controller, event) in self.esp.take_events() {
+            let axis = match event.motor() {
+                Some(motor) => match self.motion.calibration.axis_for_motor(controller, motor) {
+                    Some(axis) => Some(axis),
+                    None => {
+                        warn!(?event, "event for unknown motor");
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            match (event, axis) {
+                (EspEvent::Limit { sense, .. }, Some(axis)) => {
+                    warn!(%axis, ?sense, "limit switch triggered");
+                    self.stage.invalidate(axis);
+                    self.motor_stopped(axis);
+                    self.send_response(&Response::LimitTriggered {
+                        axis,
+                        direction: sense.as_esp().to_string(),
+                    }).await;
+                }
+                (EspEvent::Jogged { sense, steps, .. }, Some(axis)) => {
+                    // Inversion is symmetric, so mapping back from the wire is the same call
+                    let sense = self.motion.calibration.esp_sense(axis, sense);
+                    info!(%axis, ?sense, steps, "jog finished");
+                    self.stage.apply_move(axis, sense, steps);
+                }
+                (EspEvent::Stalled { .. }, Some(axis)) => {
+                    warn!(%axis, "motor stalled, position lost");
+                    self.stage.invalidate(axis);
+                    self.motor_stopped(axis);
+                    self.send_response(&Response::Stalled { axis }).await;
+                }
+                (EspEvent::DriverFault { code, .. }, Some(axis)) => {
+                    error!(%axis, %code, "driver fault");
+                    self.stage.invalidate(axis);
+                    self.motor_stopped(axis);
+                    {
+                        // Latched like an emergency stop: check_fault halts the rest of the stage
+                        let mut state = self.session_state.write().await;
+                        state.driver_faults.insert(axis, code.clone());
+                        state.fault.get_or_insert_with(|| format!("driver fault on {} axis: {}", axis, code));
+                    }
+                    self.send_response(&Response::DriverFault { axis, code }).await;
+                }
+                (EspEvent::Button { id, pressed }, _) if controller == self.motion.calibration.buttons => {
+                    info!(button = id, pressed, "button");
+                    self.session_state.write().await.buttons.insert(id, pressed);
+                    self.send_response(&Response::Button { button: id, pressed }).await;
+                }
+                (EspEvent::Sensor { name, value }, _) if controller == self.motion.calibration.sensors => {
+                    debug!(sensor = %name, value, "sensor reading");
+                    self.session_state.write().await.sensors.insert(name.clone(), value);
+                    self.send_response(&Response::SensorReading { sensor: name, value }).await;
+                }
+                (event @ (EspEvent::Button { .. } | EspEvent::Sensor { .. }), _) => {
+                    // Same ids may exist on every board; only the routed one speaks for the device
+                    warn!(controller = self.esp.get(controller).name(), ?event, "peripheral event from a controller it is not routed to");
+                }
+                (EspEvent::Done { steps, .. }, Some(axis)) => {
+                    METRICS.esp_replies.with_label_values(&["done"]).inc();
+                    self.finish_leg(axis, Ok(Completion::Done { steps })).await;
+                }
+                (EspEvent::Failed { reason, steps, .. }, Some(axis)) => {
+                    METRICS.esp_replies.with_label_values(&["fail"]).inc();
+                    self.finish_leg(axis, Ok(Completion::Failed { reason, steps })).await;
+                }
+                (EspEvent::EmergencyStopped, _) => info!("ESP32 confirmed emergency stop"),
+                (event, _) => debug!(?event, "ignoring event"),
+            }
+        }
+    }
+
+    /// The ESP32 stopped a motor on its own, so forget any continuous motion on it.
+    fn motor_stopped(&mut self, axis: Axis) {
+        // No keepalives for a jog the ESP32 already ended
+        if self.jog.as_ref().is_some_and(|jog| jog.axis == axis) {
+            self.jog = None;
+        }
+        if let Some(drive) = &mut self.drive {
+            drive.halt(axis);
+        }
+    }
+
+    async fn start_jog(&mut self, axis: Axis, sense: Sense, speed: Option<u32>) {
+        // Only one jog at a time: a new one replaces whatever is running
+        if self.jog.is_some() && !self.stop_jog().await {
+            self.send_error("JogStart: could not stop the previous jog").await;
+            return;
+        }
+
+        let speed = speed.unwrap_or(self.motion.jog_default_speed);
+        if speed == 0 || speed > self.motion.max_speed {
+            warn!(speed, "rejecting jog");
+            self.send_error(&format!(
+                "JogStart: speed {} is out of range (1..={} steps/s)",
+                speed, self.motion.max_speed
+            )).await;
+            return;
+        }
+
+        let from = self.stage.position().get(axis);
+        if let LimitCheck::Rejected(reason) = self.motion.limits.check_move(axis, from, sense, 1) {
+            warn!(%axis, %reason, "jog rejected by soft limit");
+            self.send_error(&format!("JogStart: {}", reason)).await;
+            return;
+        }
+
+        if !self.send_jog(axis, sense, speed).await {
+            self.send_error("JogStart: ESP32 did not start the jog").await;
+            return;
+        }
+        self.last_sense[axis as usize] = Some(sense);
+        let now = Instant::now();
+        self.jog = Some(Jog { axis, sense, speed, from, started: now, last_keepalive: now });
+        self.send_ack("JogStart").await;
+        self.send_status("Jogging").await;
+    }
+
+    async fn keep_jog_alive(&mut self) {
+        let Some(jog) = &mut self.jog else {
+            // Keepalives can cross a stop in flight, nothing to report
+            debug!("keepalive without a running jog");
+            return;
+        };
+        jog.last_keepalive = Instant::now();
+        let (axis, sense, speed) = (jog.axis, jog.sense, jog.speed);
+        if !self.send_jog(axis, sense, speed).await {
+            warn!(%axis, "jog keepalive not acknowledged, stopping");
+            self.stop_jog().await;
+            self.send_error("Jog stopped: ESP32 did not acknowledge the keepalive").await;
+            self.send_status("Idle").await;
+        }
+    }
+
+    /// Stop the jog on a missed keepalive, or just before it would cross a soft limit.
+    async fn check_jog(&mut self) {
+        let Some(jog) = &self.jog else { return };
+        let reason = if jog.last_keepalive.elapsed() > self.motion.jog_timeout {
+            Some(format!("no keepalive within {:?}", self.motion.jog_timeout))
+        } else {
+            // Estimated travel by the next check, so the limit is not crossed in between
+            let lookahead = jog.started.elapsed() + COMMAND_TICK;
+            let travelled = (jog.speed as f64 * lookahead.as_secs_f64()) as u32;
+            match self.motion.limits.check_move(jog.axis, jog.from, jog.sense, travelled) {
+                LimitCheck::Allowed => None,
+                LimitCheck::Clamped { reason, .. } | LimitCheck::Rejected(reason) => Some(reason),
+            }
+        };
+        let Some(reason) = reason else { return };
+
+        warn!(axis = %jog.axis, %reason, "stopping jog");
+        self.stop_jog().await;
+        self.send_error(&format!("Jog stopped: {}", reason)).await;
+        self.send_status("Idle").await;
+    }
+
+    /// Update the joystick vector; `drive_velocity` ramps the motors towards it.
+    async fn set_velocity(&mut self, vector: [f64; 3]) {
+        if let Some(v) = vector.iter().find(|v| !(v.is_finite() && (-1.0..=1.0).contains(*v))) {
+            warn!(?vector, "rejecting velocity");
+            self.send_error(&format!("SetVelocity: component {} is outside -1.0..=1.0", v)).await;
+            return;
+        }
+        if self.drive.is_none() && vector.iter().all(|&v| v == 0.0) {
+            return;
+        }
+        if self.jog.is_some() {
+            info!("stopping jog for velocity control");
+            self.stop_jog().await;
+        }
+
+        let now = Instant::now();
+        let max_speed = self.motion.max_speed as f64;
+        let target = vector.map(|v| v * max_speed);
+        if self.drive.is_none() {
+            info!(?target, "velocity control started");
+            self.drive = Some(VelocityDrive::new(now));
+            self.send_status("Moving").await;
+        }
+        if let Some(drive) = &mut self.drive {
+            drive.set_target(target, now);
+        }
+    }
+
+    /// Advance the velocity ramp one tick and pass any speed change on to the ESP32.
+    async fn drive_velocity(&mut self) {
+        let now = Instant::now();
+        let Some(drive) = &mut self.drive else { return };
+        drive.advance(now, self.motion.velocity_accel, self.motion.velocity_timeout);
+        let refresh = drive.refresh_due(now, self.motion.jog_timeout / 2);
+
+        for axis in Axis::ALL {
+            let Some(drive) = &self.drive else { return };
+            let (mut speed, sent, travelled) = (drive.speed(axis), drive.sent(axis), drive.travelled(axis));
+
+            if speed != 0 {
+                // Stop short of the soft limits, estimating travel up to the next tick
+                let from = self.stage.position().get(axis);
+                let ahead = travelled.abs() + speed.unsigned_abs() as f64 * COMMAND_TICK.as_secs_f64();
+                if let LimitCheck::Clamped { reason, .. } | LimitCheck::Rejected(reason) =
+                    self.motion.limits.check_move(axis, from, sense_of(speed), ahead as u32)
+                {
+                    if sent != 0 {
+                        warn!(%axis, %reason, "velocity stopped by soft limit");
+                        self.send_error(&format!("SetVelocity: {} axis stopped, {}", axis, reason)).await;
+                    }
+                    if let Some(drive) = &mut self.drive {
+                        drive.hold(axis);
+                    }
+                    speed = 0;
+                }
+            }
+            if speed == sent && !(speed != 0 && refresh) {
+                continue;
+            }
+
+            let mut ok = true;
+            if sent != 0 && (speed == 0 || speed.signum() != sent.signum()) {
+                ok = self.send_jog_stop(axis, sense_of(sent)).await;
+                if let Some(drive) = &mut self.drive {
+                    drive.mark_sent(axis, 0);
+                }
+            }
+            if ok && speed != 0 {
+                ok = self.send_jog(axis, sense_of(speed), speed.unsigned_abs() as u32).await;
+                if let Some(drive) = &mut self.drive {
+                    drive.mark_sent(axis, if ok { speed } else { 0 });
+                }
+                self.last_sense[axis as usize] = Some(sense_of(speed));
+            }
+            if !ok {
+                error!(%axis, speed, "ESP32 did not follow velocity change, stopping");
+                self.stop_velocity().await;
+                self.send_error(&format!("SetVelocity: ESP32 did not follow the {} axis, stopped", axis)).await;
+                self.send_status("Idle").await;
+                return;
+            }
+        }
+
+        if self.drive.as_ref().is_some_and(VelocityDrive::is_idle) {
+            info!("velocity control idle");
+            self.drive = None;
+            self.send_status("Idle").await;
+        }
+    }
+
+    /// Stop every axis under velocity control. Returns false if any stop was not confirmed.
+    async fn stop_velocity(&mut self) -> bool {
+        let Some(drive) = self.drive.take() else { return true };
+        let mut ok = true;
+        for axis in Axis::ALL {
+            let sent = drive.sent(axis);
+            if sent != 0 {
+                ok &= self.send_jog_stop(axis, sense_of(sent)).await;
+            }
+        }
+        ok
+    }
+
+    /// Report each ESP32 going away or coming back, and try to reopen those that are gone.
+    async fn check_link(&mut self) {
+        let retry = self.last_reconnect.elapsed() >= RECONNECT_INTERVAL;
+        if retry {
+            self.last_reconnect = Instant::now();
+        }
+        for controller in 0..self.esp.len() {
+            let esp = self.esp.get_mut(controller);
+            esp.watchdog().await;
+            if !esp.is_connected() && retry {
+                match esp.reconnect().await {
+                    Ok(()) => METRICS.esp_reconnects.inc(),
+                    Err(e) => {
+                        if let Some(skipped) = self.link_log.check() {
+                            warn!(controller = esp.name(), error = %e, skipped, "ESP32 still unavailable");
+                        }
+                    }
+                }
+            }
+
+            let esp = self.esp.get(controller);
+            let (name, connected) = (esp.name().to_string(), esp.is_connected());
+            METRICS.esp_connected.with_label_values(&[&name]).set(connected as i64);
+            if !connected {
+                self.defaults_sent[controller] = false;
+            } else if !self.defaults_sent[controller] {
+                self.defaults_sent[controller] = true;
+                self.send_motor_defaults(controller).await;
+            }
+            if connected == self.online[controller] {
+                continue;
+            }
+            self.online[controller] = connected;
+
+            if connected {
+                info!(controller = name, "ESP32 online, axes must be homed again");
+                if self.reset_pending[controller] && !self.faulted().await && !self.reset_controller(controller).await {
+                    warn!(controller = name, "ESP32 did not clear its emergency stop");
+                }
+                let firmware = self.esp.get(controller).firmware();
+                self.send_response(&Response::StageOnline { controller: name, firmware }).await;
+                self.send_status(if self.stage_online() { "Idle" } else { "Offline" }).await;
+            } else {
+                error!(controller = name, "ESP32 offline, motion halted");
+                // The ESP32 may have reset or been swapped, so none of its axes' positions can be trusted
+                for axis in Axis::ALL {
+                    if self.motion.calibration.controller(axis) == controller {
+                        self.finish_leg(axis, Err("ESP32 offline".to_string())).await;
+                        self.motor_stopped(axis);
+                        self.last_sense[axis as usize] = None;
+                        self.stage.invalidate(axis);
+                    }
+                }
+                self.send_response(&Response::StageOffline { controller: name }).await;
+                self.send_status("Offline").await;
+            }
+        }
+        HEALTH.set_esp_connected(self.esp.iter().all(|esp| esp.is_connected()));
+    }
+
+    /// Store the configured default speed and acceleration of each axis on a
+    /// controller, which forgets them whenever it resets.
+    async fn send_motor_defaults(&mut self, controller: usize) {
+        for axis in Axis::ALL {
+            let cal = *self.motion.calibration.axis(axis);
+            if cal.controller != controller {
+                continue;
+            }
+            for (setting, value) in [("SPEED", cal.speed), ("ACCEL", cal.accel)] {
+                let Some(value) = value else { continue };
+                match self.send_esp_command(controller, EspCommand::Set, cal.motor, setting, value).await {
+                    Some(EspReply::Ack) => debug!(%axis, setting, value, "motor default set"),
+                    _ => warn!(%axis, setting, value, "ESP32 did not store motor default"),
+                }
+            }
+        }
+    }
+
+    /// Every controller driving an axis is connected, so the whole stage can move.
+    fn stage_online(&self) -> bool {
+        Axis::ALL.into_iter().all(|axis| self.online[self.motion.calibration.controller(axis)])
+    }
+
+    /// Axes a motion command drives.
+    fn command_axes(&self, cmd: &Command) -> Vec<Axis> {
+        match cmd {
+            Command::Move { direction, .. } => vec![direction.axis()],
+            Command::Zoom { .. } => vec![Axis::Z],
+            Command::GoTo { x, y, z, .. } => [(Axis::X, x), (Axis::Y, y), (Axis::Z, z)]
+                .into_iter()
+                .filter_map(|(axis, target)| target.map(|_| axis))
+                .collect(),
+            Command::Home { axes } => axes.clone().unwrap_or_else(|| HOMING_ORDER.to_vec()),
+            Command::JogStart { axis, .. } => vec![*axis],
+            Command::JogKeepalive => self.jog.iter().map(|jog| jog.axis).collect(),
+            Command::SetVelocity { .. } => Axis::ALL.to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    async fn faulted(&self) -> bool {
+        self.session_state.read().await.fault.is_some()
+    }
+
+    /// Act on a fault the listener latched: the ESP32 has already stopped, so
+    /// drop all motion state and report it once.
+    async fn check_fault(&mut self) {
+        let fault = self.session_state.read().await.fault.clone();
+        let Some(reason) = fault else { return };
+        if self.fault_handled {
+            return;
+        }
+        self.fault_handled = true;
+        error!(%reason, "fault latched, motion halted");
+
+        self.jog = None;
+        self.drive = None;
+        self.last_sense = [None; 3];
+        // Covered by the Fault response below
+        self.deferred.clear();
+        for operation in std::mem::take(&mut self.operations) {
+            // The ESP32 has stopped it; its FAIL events no longer matter
+            self.send_response(&Response::Failed { command: operation.command, reason: "fault latched".to_string() }).await;
+        }
+        // Moves may have been cut short anywhere, so no axis position can be trusted
+        for axis in Axis::ALL {
+            self.stage.invalidate(axis);
+        }
+        self.send_response(&Response::Fault { reason }).await;
+        self.send_status("Fault").await;
+    }
+
+    async fn reset_fault(&mut self) {
+        if !self.faulted().await {
+            self.send_ack("ResetFault").await;
+            return;
+        }
+        let mut cleared = true;
+        for controller in 0..self.esp.len() {
+            if !self.esp.get(controller).is_connected() {
+                // Whatever stop it latched is cleared once it is back
+                info!(controller = self.esp.get(controller).name(), "ESP32 offline, RESET deferred");
+                self.reset_pending[controller] = true;
+                continue;
+            }
+            cleared &= self.reset_controller(controller).await;
+        }
+        self.handle_esp_events().await;
+        if !cleared {
+            self.send_error("ResetFault: ESP32 did not clear its emergency stop").await;
+            return;
+        }
+        {
+            let mut state = self.session_state.write().await;
+            state.fault = None;
+            state.driver_faults.clear();
+        }
+        self.fault_handled = false;
+        info!("fault cleared, axes must be homed again");
+        self.send_ack("ResetFault").await;
+        self.send_status(if self.stage_online() { "Idle" } else { "Offline" }).await;
+    }
+
+    /// Clear the emergency stop of one controller.
+    async fn reset_controller(&mut self, controller: usize) -> bool {
+        self.reset_pending[controller] = false;
+        let esp = self.esp.get_mut(controller);
+        match esp.send_with_retry(&EspMessage::command(EspCommand::Reset).to_string()).await {
+            Ok(EspReply::Ack) => true,
+            Ok(EspReply::Err) => false,
+            Err(e) => {
+                error!(controller = esp.name(), error = %e, "failed to send RESET");
+                false
+            }
+        }
+    }
+
+    /// Start or refresh a jog on the ESP32.
+    async fn send_jog(&mut self, axis: Axis, sense: Sense, speed: u32) -> bool {
+        if self.faulted().await {
+            return false;
+        }
+        let (controller, motor) = self.motion.calibration.route(axis);
+        let esp_sense = self.motion.calibration.esp_sense(axis, sense);
+        let reply = self.send_esp_command(controller, EspCommand::Jog, motor, esp_sense.as_esp(), speed).await;
+        self.handle_esp_events().await;
+        reply == Some(EspReply::Ack)
+    }
+
+    /// Stop the running jog, if any.
+    async fn stop_jog(&mut self) -> bool {
+        let Some(jog) = self.jog.take() else { return true };
+        self.send_jog_stop(jog.axis, jog.sense).await
+    }
+
+    /// Stop a continuously moving motor and apply the distance the ESP32 reports.
+    /// Returns false if the ESP32 did not confirm, leaving the axis position unknown.
+    async fn send_jog_stop(&mut self, axis: Axis, sense: Sense) -> bool {
+        let (controller, motor) = self.motion.calibration.route(axis);
+        let esp_sense = self.motion.calibration.esp_sense(axis, sense);
+        let reply = self.send_esp_command(controller, EspCommand::JogStop, motor, esp_sense.as_esp(), 0).await;
+        // The JOGGED report arrives before the ACK
+        self.handle_esp_events().await;
+        if reply != Some(EspReply::Ack) {
+            error!(%axis, "jog stop not confirmed, position lost");
+            self.stage.invalidate(axis);
+            self.last_sense[axis as usize] = None;
+            return false;
+        }
+        true
+    }
+
+    async fn send_status(&mut self, status: &str) {
+        let steps = self.stage.position();
+        let cal = &self.motion.calibration;
+        let position_um = Some(PositionUm {
+            x: cal.steps_to_um(Axis::X, steps.x),
+            y: cal.steps_to_um(Axis::Y, steps.y),
+            z: cal.steps_to_um(Axis::Z, steps.z),
+        });
+        let homed = Some(self.stage.homed_axes());
+        self.send_response(&Response::Status {
+            status: status.to_string(),
+            position: Some(steps),
+            position_um,
+            homed,
+            controllers: self
+                .esp
+                .iter()
+                .map(|esp| {
+                    let connected = esp.is_connected();
+                    ControllerStatus {
+                        name: esp.name().to_string(),
+                        connected,
+                        firmware: if connected { esp.firmware() } else { None },
+                        link: connected.then(|| esp.link_health()),
+                    }
+                })
+                .collect(),
+        }).await;
+    }
+
+    fn validate_motion(&self, axis: Axis, params: &MoveParams) -> Result<u32, String> {
+        let steps = match (params.steps, params.distance_um) {
+            (Some(_), Some(_)) => return Err("give either steps or distance_um, not both".to_string()),
+            (Some(steps), None) => steps,
+            (None, Some(um)) if um.is_finite() && um > 0.0 => {
+                let steps = self.motion.calibration.um_to_steps(axis, um);
+                u32::try_from(steps).map_err(|_| format!("distance {} um is out of range", um))?
+            }
+            (None, Some(um)) => return Err(format!("distance_um must be positive, got {}", um)),
+            (None, None) => self.motion.default_steps,
+        };
+        if steps == 0 || steps > self.motion.max_steps {
+            return Err(format!("steps must be between 1 and {}, got {}", self.motion.max_steps, steps));
+        }
+        if let Some(speed) = params.speed.filter(|&s| s == 0 || s > self.motion.max_speed) {
+            return Err(format!("speed must be between 1 and {}, got {}", self.motion.max_speed, speed));
+        }
+        if let Some(accel) = params.accel.filter(|&a| a == 0 || a > self.motion.max_accel) {
+            return Err(format!("accel must be between 1 and {}, got {}", self.motion.max_accel, accel));
+        }
+        Ok(steps)
     }
 
     async fn send_heartbeat(&mut self) {
@@ -138,30 +1381,60 @@ where
             "heartbeat": "alive"
         });
         if let Err(e) = self.write.send(Message::Text(heartbeat.to_string())).await {
-            eprintln!("[Processor] ❌ Failed to send heartbeat: {}", e);
+            error!(error = %e, "failed to send heartbeat");
         } else {
-            println!("[Processor] ❤️ Sent heartbeat.");
+            debug!("sent heartbeat");
         }
     }
 
-    async fn send_esp_command(&mut self, cmd: &str, motor: u8, direction: &str, steps: u32) {
-        let msg = EspMessage {
-            cmd: cmd.to_string(),
-            motor: Some(motor),
-            direction: Some(direction.to_string()),
-            steps: Some(steps),
-        };
+    async fn send_esp_command(&mut self, controller: usize, cmd: EspCommand, motor: u8, direction: &str, steps: u32) -> Option<EspReply> {
+        match EspMessage::new(cmd, motor, direction, steps) {
+            Ok(msg) => self.send_esp_message(controller, msg).await,
+            Err(e) => {
+                error!(esp_command = %cmd, error = %e, "cannot build ESP command");
+                None
+            }
+        }
+    }
+
+    async fn send_esp_message(&mut self, controller: usize, msg: EspMessage) -> Option<EspReply> {
+        let esp = self.esp.get_mut(controller);
+        if !esp.supports(msg.cmd) {
+            error!(controller = esp.name(), esp_command = %msg.cmd, "ESP32 firmware does not support command");
+            return None;
+        }
 
         let msg_str = msg.to_string();
-        println!("[Processor] Sending ESP command: {}", msg_str);
+        debug!(esp_command = %msg_str, "sending ESP command");
 
-        if let Err(e) = self.esp.send_with_retry(&msg_str).await {
-            eprintln!("[Processor] ❌ Failed to send ESP command '{}': {}", msg_str, e);
+        match esp.send_with_retry(&msg_str).await {
+            Ok(reply) => Some(reply),
+            Err(e) => {
+                error!(esp_command = %msg_str, error = %e, "failed to send ESP command");
+                None
+            }
         }
     }
 
+    async fn send_response(&mut self, response: &Response) {
+        let text = match serde_json::to_string(response) {
+            Ok(text) => text,
+            Err(e) => {
+                error!(error = %e, ?response, "failed to serialize response");
+                return;
+            }
+        };
+        if let Err(e) = self.write.send(Message::Text(text)).await {
+            error!(error = %e, "failed to send response");
+        }
+    }
+
+    async fn send_error(&mut self, message: &str) {
+        self.send_response(&Response::Error { message: message.to_string() }).await;
+    }
+
     async fn send_ack(&mut self, cmd: &str) {
-        println!("[Processor] Sending ACK for command: {}", cmd);
+        debug!(command = cmd, "sending ACK");
         let ack = json!({
             "status": "ACK",
             "command": cmd,
@@ -170,12 +1443,10 @@ where
     }
 
     async fn send_image_frame(&mut self) {
-        println!("[Processor] Sending image frame...");
-
         let frame_guard = self.latest_frame.read().await;
 
         if frame_guard.is_empty() {
-            println!("[Processor] No frame available to send.");
+            warn!("no frame available to capture");
             return;
         }
 
@@ -193,20 +1464,23 @@ where
 
 
         if let Err(e) = self.write.send(Message::Text(payload.to_string())).await {
-            eprintln!("[Processor] Failed to send image frame: {}", e);
+            error!(error = %e, "failed to send captured image");
         } else {
-            println!("[Processor] ✅ Sent image frame ({} bytes).", encoded.len());
+            info!(bytes = encoded.len(), "sent captured image");
         }
 
     }
 
     async fn send_stream_frame(&mut self) {
-        println!("[Processor] Sending stream frame...");
-
+        let send_timer = METRICS.frame_send_seconds.start_timer();
         let frame_guard = self.latest_frame.read().await;
 
         if frame_guard.is_empty() {
-            println!("[Processor] No frame available to send.");
+            send_timer.stop_and_discard();
+            METRICS.frames_dropped.with_label_values(&["no_frame"]).inc();
+            if let Some(skipped) = self.frame_log.check() {
+                debug!(skipped, "no stream frame available yet");
+            }
             return;
         }
 
@@ -222,10 +1496,339 @@ where
         });
 
         if let Err(e) = self.write.send(Message::Text(payload.to_string())).await {
-            eprintln!("[Processor] Failed to send image frame: {}", e);
+            send_timer.stop_and_discard();
+            METRICS.frames_dropped.with_label_values(&["send_error"]).inc();
+            error!(error = %e, "failed to send stream frame");
         } else {
-            println!("[Processor] ✅ Sent image frame ({} bytes).", encoded.len());
+            send_timer.observe_duration();
+            METRICS.frames_sent.inc();
+            METRICS.frame_bytes.observe(encoded.len() as f64);
+            if let Some(skipped) = self.frame_log.check() {
+                debug!(bytes = encoded.len(), skipped, "sent stream frame");
+            }
+        }
+
+    }
+}
+
+/// Direction of a signed speed (forward positive).
+fn sense_of(speed: i64) -> Sense {
+    if speed > 0 { Sense::Forward } else { Sense::Backward }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::esp32::{frame, EspHandler, SerialHandler};
+    use crate::motion::backlash::{Backlash, BacklashMode};
+    use crate::motion::calibration::{AxisCalibration, Calibration};
+    use crate::motion::limits::{AxisLimits, LimitMode, SoftLimits};
+    use futures::channel::mpsc::{self as channel, SendError, UnboundedReceiver, UnboundedSender};
+    use futures::sink::SinkMapErr;
+    use futures::{FutureExt, StreamExt};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite;
+
+    type Backend = SinkMapErr<UnboundedSender<Message>, fn(SendError) -> tungstenite::Error>;
+
+    /// ESP32 at the other end of a duplex stream: ACKs every command and
+    /// sends whatever events the test gives it.
+    struct FakeEsp {
+        received: mpsc::UnboundedReceiver<String>,
+        events: mpsc::UnboundedSender<String>,
+    }
+
+    impl FakeEsp {
+        fn start() -> (EspHandler, FakeEsp) {
+            let (host, device) = tokio::io::duplex(1024);
+            let (received_tx, received) = mpsc::unbounded_channel();
+            let (events, mut events_rx) = mpsc::unbounded_channel::<String>();
+            tokio::spawn(async move {
+                let (read, mut write) = tokio::io::split(device);
+                let mut lines = BufReader::new(read).lines();
+                let mut seq = 0u16;
+                loop {
+                    let body = tokio::select! {
+                        line = lines.next_line() => {
+                            let Ok(Some(line)) = line else { break };
+                            let frame = frame::decode(line.trim()).unwrap();
+                            let _ = received_tx.send(frame.body);
+                            format!("ACK:{}", frame.seq)
+                        }
+                        // Dropping the FakeEsp unplugs the board
+                        event = events_rx.recv() => match event {
+                            Some(event) => event,
+                            None => break,
+                        },
+                    };
+                    seq = seq.wrapping_add(1);
+                    if write.write_all(format!("{}\n", frame::encode(seq, &body)).as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            (EspHandler::new(SerialHandler::from_io(host)), FakeEsp { received, events })
         }
 
+        /// Commands received since the last call, oldest first.
+        fn received(&mut self) -> Vec<String> {
+            std::iter::from_fn(|| self.received.try_recv().ok()).collect()
+        }
+
+        fn send(&self, event: &str) {
+            self.events.send(event.to_string()).unwrap();
+        }
+    }
+
+    /// X and Y on the first board, Z on the second.
+    fn motion(test: &str, backlash: Backlash) -> MotionConfig {
+        let axis = |controller, motor| AxisCalibration { controller, motor, steps_per_um: 1.0, invert: false, speed: None, accel: None };
+        let state_path = std::env::temp_dir().join(format!("bsmanager-processor-{}-{}.json", std::process::id(), test));
+        let _ = std::fs::remove_file(&state_path);
+        MotionConfig {
+            default_steps: 100,
+            max_steps: 10_000,
+            max_speed: 5_000,
+            max_accel: 10_000,
+            state_path,
+            homing_timeout: Duration::from_secs(10),
+            move_timeout: Duration::from_secs(10),
+            jog_default_speed: 1_000,
+            jog_timeout: Duration::from_secs(10),
+            velocity_accel: 1_000.0,
+            velocity_timeout: Duration::from_secs(1),
+            limits: SoftLimits {
+                x: AxisLimits::default(),
+                y: AxisLimits::default(),
+                z: AxisLimits::default(),
+                z_objective_max: None,
+                mode: LimitMode::Clamp,
+            },
+            calibration: Calibration { x: axis(0, 1), y: axis(0, 2), z: axis(1, 1), buttons: 0, sensors: 0 },
+            backlash,
+        }
+    }
+
+    fn no_backlash() -> Backlash {
+        Backlash { x: 0, y: 0, z: 0, mode: BacklashMode::TakeUp }
+    }
+
+    struct Harness {
+        processor: Processor<Backend>,
+        commands: mpsc::Sender<Command>,
+        responses: UnboundedReceiver<Message>,
+        boards: Vec<FakeEsp>,
+    }
+
+    impl Harness {
+        fn new(motion: MotionConfig) -> Self {
+            let (handlers, boards): (Vec<_>, Vec<_>) = (0..2).map(|_| FakeEsp::start()).unzip();
+            let (commands, rx) = mpsc::channel(64);
+            let (write, responses) = channel::unbounded();
+            let write: Backend = write.sink_map_err(|_| tungstenite::Error::ConnectionClosed);
+            let stage = Stage::load(&motion.state_path);
+            let processor = Processor::new(
+                rx,
+                write,
+                Arc::new(RwLock::new(SessionState::new())),
+                Arc::new(RwLock::new(Vec::new())),
+                Controllers::from_handlers(handlers),
+                stage,
+                motion,
+            );
+            Self { processor, commands, responses, boards }
+        }
+
+        async fn send(&self, json: &str) {
+            self.commands.send(serde_json::from_str(json).unwrap()).await.unwrap();
+        }
+
+        /// One command tick, once the boards' latest frames have come through.
+        async fn tick(&mut self) {
+            time::sleep(Duration::from_millis(20)).await;
+            self.processor.command_tick().await;
+        }
+
+        /// Outcome responses since the last call; Status and the like are left out.
+        fn responses(&mut self) -> Vec<String> {
+            std::iter::from_fn(|| self.responses.next().now_or_never().flatten())
+                .filter_map(|message| {
+                    let Message::Text(text) = message else { return None };
+                    Some(match serde_json::from_str(&text).ok()? {
+                        Response::Accepted { command } => format!("accepted {}", command),
+                        Response::Completed { command } => format!("completed {}", command),
+                        Response::Failed { command, reason } => format!("failed {}: {}", command, reason),
+                        Response::Error { message } => format!("error {}", message),
+                        Response::Fault { reason } => format!("fault {}", reason),
+                        Response::StageOffline { controller } => format!("offline {}", controller),
+                        _ => return None,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_for_a_busy_board_run_in_order_once_it_is_free() {
+        let mut h = Harness::new(motion("deferral", no_backlash()));
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 100}"#).await;
+        h.tick().await;
+        h.send(r#"{"type": "Move", "direction": "up", "steps": 200}"#).await;
+        h.send(r#"{"type": "Move", "direction": "right", "steps": 300}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:100"]);
+        assert_eq!(h.responses(), ["accepted Move left"]);
+
+        h.boards[0].send("DONE:1:FWD:100");
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:2:FWD:200"]);
+        assert_eq!(h.responses(), ["completed Move left", "accepted Move up"]);
+
+        h.boards[0].send("DONE:2:FWD:200");
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:BWD:300"]);
+        assert_eq!(h.responses(), ["completed Move up", "accepted Move right"]);
+    }
+
+    #[tokio::test]
+    async fn boards_move_at_the_same_time() {
+        let mut h = Harness::new(motion("overlap", no_backlash()));
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 100}"#).await;
+        h.send(r#"{"type": "Zoom", "direction": "in", "steps": 50}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:100"]);
+        assert_eq!(h.boards[1].received(), ["MOVE:1:FWD:50"]);
+        assert_eq!(h.responses(), ["accepted Move left", "accepted Zoom"]);
+
+        // The zoom finishes first, while the move carries on
+        h.boards[1].send("DONE:1:FWD:50");
+        h.tick().await;
+        assert_eq!(h.responses(), ["completed Zoom"]);
+        h.boards[0].send("DONE:1:FWD:100");
+        h.tick().await;
+        assert_eq!(h.responses(), ["completed Move left"]);
+        assert_eq!((h.processor.stage.position().x, h.processor.stage.position().z), (100, 50));
+    }
+
+    #[tokio::test]
+    async fn fault_drops_held_back_commands_with_a_single_report() {
+        let mut h = Harness::new(motion("fault", no_backlash()));
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 100}"#).await;
+        h.tick().await;
+        h.send(r#"{"type": "Move", "direction": "up", "steps": 200}"#).await;
+        h.send(r#"{"type": "Move", "direction": "right", "steps": 300}"#).await;
+        h.tick().await;
+        assert_eq!(h.responses(), ["accepted Move left"]);
+
+        h.processor.session_state.write().await.fault = Some("emergency stop".to_string());
+        h.tick().await;
+        assert_eq!(h.responses(), ["failed Move left: fault latched", "fault emergency stop"]);
+
+        // Neither the late DONE nor the freed board starts the held-back moves
+        h.boards[0].send("DONE:1:FWD:100");
+        h.send(r#"{"type": "Move", "direction": "up", "steps": 10}"#).await;
+        h.send(r#"{"type": "Zoom", "direction": "in", "steps": 10}"#).await;
+        h.tick().await;
+        h.tick().await;
+        assert_eq!(h.responses(), ["fault emergency stop"]);
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:100"]);
+        assert!(h.boards[1].received().is_empty());
+    }
+
+    #[tokio::test]
+    async fn offline_board_refuses_only_its_own_axes() {
+        let mut h = Harness::new(motion("offline", no_backlash()));
+        drop(h.boards.pop());
+        h.tick().await;
+        assert_eq!(h.responses(), ["offline main"]);
+
+        h.send(r#"{"type": "Zoom", "direction": "in", "steps": 50}"#).await;
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 100}"#).await;
+        h.tick().await;
+        assert_eq!(h.responses(), ["error Zoom: stage offline", "accepted Move left"]);
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:100"]);
+    }
+
+    #[tokio::test]
+    async fn jog_stop_cancels_a_jog_waiting_for_its_board() {
+        let mut h = Harness::new(motion("jog", no_backlash()));
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 100}"#).await;
+        h.tick().await;
+        h.send(r#"{"type": "JogStart", "axis": "y", "direction": "forward"}"#).await;
+        h.send(r#"{"type": "JogKeepalive"}"#).await;
+        h.send(r#"{"type": "JogStop"}"#).await;
+        h.tick().await;
+
+        h.boards[0].send("DONE:1:FWD:100");
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:100"]);
+        assert!(h.processor.jog.is_none());
+    }
+
+    /// Home X so its position can be trusted, leaving the slack on the backward side.
+    async fn homed_x(test: &str, mode: BacklashMode) -> Harness {
+        let mut h = Harness::new(motion(test, Backlash { x: 10, y: 0, z: 0, mode }));
+        h.send(r#"{"type": "Home", "axes": ["x"]}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["HOME:1:BWD:0"]);
+        h.boards[0].send("DONE:1:BWD:0");
+        h.tick().await;
+        assert!(h.processor.stage.is_homed(Axis::X));
+        h.responses();
+        h
+    }
+
+    #[tokio::test]
+    async fn failed_move_counts_only_steps_past_the_slack() {
+        let mut h = homed_x("take-up", BacklashMode::TakeUp).await;
+        h.send(r#"{"type": "Move", "direction": "left", "steps": 50}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:FWD:60"]);
+
+        h.boards[0].send("FAIL:1:LIMIT:30");
+        h.tick().await;
+        assert_eq!(h.responses(), ["accepted Move left", "failed Move left: stopped by ESP32 (LIMIT) after 30 of 60 steps"]);
+        assert_eq!(h.processor.stage.position().x, 20);
+        assert!(h.processor.stage.is_homed(Axis::X));
+    }
+
+    #[tokio::test]
+    async fn failing_in_the_approach_overshoot_unhomes_the_axis() {
+        let mut h = homed_x("overshoot", BacklashMode::Approach(Sense::Forward)).await;
+        h.send(r#"{"type": "Move", "direction": "right", "steps": 100}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:BWD:110"]);
+
+        // Short of the target, the position still holds
+        h.boards[0].send("FAIL:1:STOP:60");
+        h.tick().await;
+        assert_eq!(h.processor.stage.position().x, -60);
+        assert!(h.processor.stage.is_homed(Axis::X));
+
+        // Past it, somewhere in the overshoot
+        h.send(r#"{"type": "Move", "direction": "right", "steps": 100}"#).await;
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:BWD:110"]);
+        h.boards[0].send("FAIL:1:STOP:105");
+        h.tick().await;
+        assert_eq!(h.processor.stage.position().x, -165);
+        assert!(!h.processor.stage.is_homed(Axis::X));
+    }
+
+    #[tokio::test]
+    async fn failing_on_the_way_back_from_the_overshoot_unhomes_the_axis() {
+        let mut h = homed_x("return", BacklashMode::Approach(Sense::Forward)).await;
+        h.send(r#"{"type": "Move", "direction": "right", "steps": 100}"#).await;
+        h.tick().await;
+        h.boards[0].send("DONE:1:BWD:110");
+        h.tick().await;
+        assert_eq!(h.boards[0].received(), ["MOVE:1:BWD:110", "MOVE:1:FWD:20"]);
+
+        h.boards[0].send("FAIL:1:STOP:5");
+        h.tick().await;
+        assert_eq!(h.responses(), ["accepted Move right", "failed Move right: stopped by ESP32 (STOP) after 5 of 20 steps"]);
+        assert_eq!(h.processor.stage.position().x, -100);
+        assert!(!h.processor.stage.is_homed(Axis::X));
     }
 }
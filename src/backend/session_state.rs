@@ -1,10 +1,19 @@
+use std::collections::HashMap;
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
+use crate::backend::models::Axis;
+
 pub struct SessionState {
     pub connected: bool,
     pub cancel_token: CancellationToken, // token per session
+    pub session_id: Option<Uuid>,       // generated on StartStream, used to tag logs
     pub microscope_id: Option<Uuid>,   
+    pub fault: Option<String>,          // latched by EmergencyStop, only cleared by ResetFault
+    // Reported by the ESP32; like `fault`, these describe the hardware and outlive the session
+    pub driver_faults: HashMap<Axis, String>, // driver fault code per axis, cleared by ResetFault
+    pub buttons: HashMap<u8, bool>,     // last known state per button, true = pressed
+    pub sensors: HashMap<String, u32>,  // latest raw reading per sensor
 }
 
 impl SessionState {
@@ -12,15 +21,22 @@ impl SessionState {
         Self {
             connected: false,
             cancel_token: CancellationToken::new(),
+            session_id: None,
             microscope_id: None,
+            fault: None,
+            driver_faults: HashMap::new(),
+            buttons: HashMap::new(),
+            sensors: HashMap::new(),
         }
     }
 
-    /// Reset session: cancel tasks and create new token
+    /// Reset session: cancel tasks and create new token. A latched fault
+    /// outlives the session, since the hardware is still stopped.
     pub fn reset(&mut self) {
         self.connected = false;
         self.cancel_token.cancel(); // cancel all session-scoped tasks
         self.cancel_token = CancellationToken::new(); // fresh token for next session
+        self.session_id = None;
         self.microscope_id = None;   // clear microscope ID
     }
 }
\ No newline at end of file
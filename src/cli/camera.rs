@@ -0,0 +1,49 @@
+use crate::backend::session_state::SessionState;
+use crate::config::Config;
+use crate::controllers::camera::Camera;
+
+use anyhow::Context;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{sleep, Duration};
+
+/// Open the camera and save `count` JPEG frames into `output_dir`.
+pub async fn snapshot(config: &Config, count: u32, interval_ms: u64, output_dir: &str) -> anyhow::Result<()> {
+    std::fs::create_dir_all(output_dir).with_context(|| format!("creating {}", output_dir))?;
+
+    let session_state = Arc::new(RwLock::new(SessionState::new()));
+    let mut cam = Camera::new(config.camera.index, config.camera.width, config.camera.height);
+    session_state.write().await.connected = true; // Simulate a connected state
+    cam.spawn_task(Arc::clone(&session_state));
+
+    let mut saved = 0;
+    for i in 1..=count {
+        sleep(Duration::from_millis(interval_ms)).await;
+
+        let frame_data = cam.latest_frame();
+        let buf = frame_data.read().await;
+        if buf.is_empty() {
+            println!("No frame captured at iteration {}", i);
+            continue;
+        }
+
+        let filename = Path::new(output_dir).join(format!("frame_{:02}.jpg", i));
+        match std::fs::write(&filename, &*buf) {
+            Ok(()) => {
+                println!("Saved {}", filename.display());
+                saved += 1;
+            }
+            Err(e) => eprintln!("Failed to save {}: {}", filename.display(), e),
+        }
+    }
+
+    // Stop camera
+    cam.stop();
+    println!("Camera stopped.");
+
+    if saved == 0 {
+        anyhow::bail!("no frames captured from camera {}", config.camera.index);
+    }
+    Ok(())
+}
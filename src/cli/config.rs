@@ -0,0 +1,97 @@
+use crate::config::Config;
+
+/// Print the effective configuration and fail if the daemon could not start with it.
+pub fn check(config: &Config) -> anyhow::Result<()> {
+    let b = &config.backend;
+    println!("Backend:");
+    println!("  host:        {}", b.host.as_deref().unwrap_or("(unset)"));
+    println!("  port:        {}", b.port.as_deref().unwrap_or("(unset)"));
+    println!("  device name: {}", b.device_name.as_deref().unwrap_or("(unset)"));
+    println!("  auth token:  {}", if b.auth_token.is_some() { "(set)" } else { "(unset)" });
+    for serial in &config.controllers {
+        println!("Serial ({}):", serial.name);
+        println!("  port:        {}", serial.port);
+        println!("  baud rate:   {}", serial.baud_rate);
+        match &serial.usb {
+            Some(usb) => println!(
+                "  usb match:   vid {}, pid {}, serial {}",
+                usb.vid.map_or("any".to_string(), |v| format!("{:04x}", v)),
+                usb.pid.map_or("any".to_string(), |p| format!("{:04x}", p)),
+                usb.serial.as_deref().unwrap_or("any"),
+            ),
+            None => println!("  usb match:   (none, using port)"),
+        }
+        println!("  min firmware: {}", serial.min_firmware);
+        println!("  ping:        every {:?} idle, lost after {} misses{}",
+            serial.ping_interval,
+            serial.ping_failures,
+            if serial.reset_on_loss { ", then reset" } else { "" });
+        match &serial.capture {
+            Some(capture) => println!(
+                "  capture:     {}/{}.jsonl, {} KiB x {} files",
+                capture.dir.display(),
+                serial.name,
+                capture.max_bytes / 1024,
+                capture.files + 1
+            ),
+            None => println!("  capture:     (off)"),
+        }
+    }
+    println!("Camera:");
+    println!("  index:       {}", config.camera.index);
+    println!("  resolution:  {}x{}", config.camera.width, config.camera.height);
+    println!("Motion:");
+    println!("  default steps: {}", config.motion.default_steps);
+    println!("  max steps:     {}", config.motion.max_steps);
+    println!("  max speed:     {} steps/s", config.motion.max_speed);
+    println!("  max accel:     {} steps/s²", config.motion.max_accel);
+    println!("  state file:    {}", config.motion.state_path.display());
+    println!("  homing timeout: {:?}", config.motion.homing_timeout);
+    println!("  move timeout:  {:?}", config.motion.move_timeout);
+    println!("  jog speed:     {} steps/s", config.motion.jog_default_speed);
+    println!("  jog timeout:   {:?}", config.motion.jog_timeout);
+    println!("  velocity accel: {} steps/s²", config.motion.velocity_accel);
+    println!("  velocity timeout: {:?}", config.motion.velocity_timeout);
+    let limits = &config.motion.limits;
+    for (name, axis) in [("x", &limits.x), ("y", &limits.y), ("z", &limits.z)] {
+        println!("  {} limits:      {:?} .. {:?}", name, axis.min, axis.max);
+    }
+    println!("  z objective limit: {:?}", limits.z_objective_max);
+    println!("  limit mode:    {:?}", limits.mode);
+    let cal = &config.motion.calibration;
+    for (name, axis) in [("x", &cal.x), ("y", &cal.y), ("z", &cal.z)] {
+        println!(
+            "  {} axis:        {} motor {}, {} steps/um{}",
+            name,
+            config.controllers[axis.controller].name,
+            axis.motor,
+            axis.steps_per_um,
+            if axis.invert { ", inverted" } else { "" }
+        );
+        println!(
+            "                 speed {}, accel {}",
+            axis.speed.map_or("firmware default".to_string(), |s| format!("{} steps/s", s)),
+            axis.accel.map_or("firmware default".to_string(), |a| format!("{} steps/s²", a)),
+        );
+    }
+    println!("  buttons:       {}", config.controllers[cal.buttons].name);
+    println!("  sensors:       {}", config.controllers[cal.sensors].name);
+    let backlash = &config.motion.backlash;
+    println!("  backlash:      x {}, y {}, z {} steps", backlash.x, backlash.y, backlash.z);
+    println!("  backlash mode: {:?}", backlash.mode);
+    println!("Monitoring:");
+    match config.monitoring.addr {
+        Some(addr) => println!("  address:     {}", addr),
+        None => println!("  address:     (disabled)"),
+    }
+
+    let problems = config.problems();
+    if problems.is_empty() {
+        println!("✅ Configuration OK");
+        return Ok(());
+    }
+    for problem in &problems {
+        eprintln!("❌ {}", problem);
+    }
+    anyhow::bail!("{} configuration problem(s)", problems.len())
+}
@@ -0,0 +1,166 @@
+use crate::config::SerialConfig;
+use crate::esp32::capture::{self, Capture, Direction};
+use crate::esp32::{discovery, frame, EspCommand, EspHandler, EspMessage, EspReply, SerialHandler};
+
+use anyhow::Context;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::Path;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::{self, Duration, Instant};
+
+async fn open(config: &SerialConfig) -> anyhow::Result<(String, EspHandler)> {
+    let capture = match &config.capture {
+        Some(capture) => Some(Capture::start(capture, &config.name).context("Failed to start serial capture")?),
+        None => None,
+    };
+    let (path, serial) = discovery::open(config, capture)
+        .await
+        .with_context(|| format!("Failed to open the serial port of controller '{}'", config.name))?;
+    Ok((path, EspHandler::new(serial)))
+}
+
+/// Send a single message and wait for ACK/ERR.
+pub async fn send(config: &SerialConfig, message: &str) -> anyhow::Result<()> {
+    let parsed: EspMessage = message
+        .parse()
+        .with_context(|| format!("'{}' is not a valid ESP32 message", message))?;
+    let (_, mut esp) = open(config).await?;
+    let reply = esp.send_with_retry(&parsed.to_string())
+        .await
+        .context("Command failed after retries")?;
+    if reply == EspReply::Err {
+        anyhow::bail!("ESP32 rejected '{}' (ERR)", message);
+    }
+    println!("Command succeeded!");
+    Ok(())
+}
+
+/// Read messages from stdin and send them to the ESP32 until `exit`.
+pub async fn repl(config: &SerialConfig) -> anyhow::Result<()> {
+    let (path, mut esp) = open(config).await?;
+    println!("Connected to ESP32 on {}", path);
+    match esp.handshake(config.min_firmware).await {
+        Ok(info) => println!("Firmware {} on board {} ({})", info.version, info.board, info.commands.join(",")),
+        Err(e) => println!("Firmware check failed: {}", e),
+    }
+
+    loop {
+        print!("Enter command (CMD:MOTOR:DIRECTION:STEPS) or 'exit': ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            break; // EOF
+        }
+        let input = input.trim();
+
+        if input.is_empty() { continue; }
+        if input.eq_ignore_ascii_case("exit") { break; }
+        if let Err(e) = input.parse::<EspMessage>() {
+            println!("Invalid message: {}", e);
+            continue;
+        }
+
+        match esp.send_with_retry(input).await {
+            Ok(EspReply::Ack) => println!("Command succeeded!"),
+            Ok(EspReply::Err) => println!("ESP32 replied ERR"),
+            Err(e) => eprintln!("Command failed: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Play a capture back through an in-memory port: the ESP32's side byte for
+/// byte at the recorded times, and the host's commands through `EspHandler`
+/// at theirs, printing what the handler makes of it.
+pub async fn replay(path: &str, speed: f64) -> anyhow::Result<()> {
+    if !(speed.is_finite() && speed > 0.0) {
+        anyhow::bail!("--speed must be positive");
+    }
+    let records = capture::read(Path::new(path))?;
+    let Some(start) = records.first().map(|r| r.ts_us) else {
+        println!("{} is empty", path);
+        return Ok(());
+    };
+    let offset = |ts_us: i64| Duration::from_secs_f64((ts_us - start).max(0) as f64 / 1e6 / speed);
+
+    // The ESP32's output as recorded, and the host's commands reassembled into
+    // frames; a repeated sequence number is a retry, which the handler makes itself
+    let mut device_output = Vec::new();
+    let mut commands = Vec::new();
+    let mut partial = Vec::new();
+    let mut seen = HashSet::new();
+    for record in &records {
+        let bytes = record.bytes().map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+        let at = offset(record.ts_us);
+        match record.dir {
+            Direction::Rx => device_output.push((at, bytes)),
+            Direction::Tx => {
+                partial.extend_from_slice(&bytes);
+                while let Some(end) = partial.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = partial.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line);
+                    match frame::decode(&line) {
+                        Ok(frame) if seen.insert(frame.seq) => commands.push((at, frame)),
+                        Ok(_) => {}
+                        Err(e) => println!("Skipping host line {:?}: {}", line.trim_end(), e),
+                    }
+                }
+            }
+        }
+    }
+    println!("Replaying {} records ({} host commands) from {}", records.len(), commands.len(), path);
+
+    let (host, device) = tokio::io::duplex(64 * 1024);
+    let (mut device_read, mut device_write) = tokio::io::split(device);
+    let mut esp = EspHandler::new(SerialHandler::from_io(host));
+    let started = Instant::now();
+
+    let playback = tokio::spawn(async move {
+        for (at, bytes) in device_output {
+            time::sleep_until(started + at).await;
+            if device_write.write_all(&bytes).await.is_err() {
+                break;
+            }
+        }
+        // Handed back so the port stays open until the host side is done too
+        device_write
+    });
+    // The handler's output is the replayed commands themselves; just keep the pipe flowing
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while matches!(device_read.read(&mut buf).await, Ok(n) if n > 0) {}
+    });
+
+    for (at, frame) in commands {
+        time::sleep_until(started + at).await;
+        print_events(&mut esp, started);
+        esp.set_next_seq(frame.seq);
+        let sent = Instant::now();
+        let result = match frame.body.parse::<EspMessage>() {
+            // Sent without waiting for a reply, as the listener does
+            Ok(msg) if msg.cmd == EspCommand::EStop => esp.emergency_stop().trigger().await.map(|()| EspReply::Ack),
+            _ => esp.send_with_retry(&frame.body).await,
+        };
+        println!(
+            "{:>9.3}s  {} (seq {}) -> {:?} after {:?}",
+            started.elapsed().as_secs_f64(),
+            frame.body,
+            frame.seq,
+            result,
+            sent.elapsed()
+        );
+    }
+    let _device_write = playback.await?;
+    // Give the reader task a moment with the last bytes
+    time::sleep(Duration::from_millis(100)).await;
+    print_events(&mut esp, started);
+    Ok(())
+}
+
+fn print_events(esp: &mut EspHandler, started: Instant) {
+    for event in esp.take_events() {
+        println!("{:>9.3}s  event {:?}", started.elapsed().as_secs_f64(), event);
+    }
+}
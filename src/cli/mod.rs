@@ -0,0 +1,104 @@
+pub mod camera;
+pub mod config;
+pub mod esp;
+pub mod probe;
+pub mod run;
+
+use clap::{Parser, Subcommand};
+use crate::config::Config;
+use crate::logging;
+
+/// BSManager: BioScope device daemon and hardware tools.
+#[derive(Debug, Parser)]
+#[command(name = "orangepi-IA", version)]
+pub struct Cli {
+    /// Load configuration from this env file instead of `./.env`
+    #[arg(long, global = true)]
+    pub env_file: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    /// Run the device daemon (default when no subcommand is given)
+    Run,
+    /// Camera tools
+    #[command(subcommand)]
+    Camera(CameraCommand),
+    /// ESP32 tools
+    #[command(subcommand)]
+    Esp(EspCommand),
+    /// List serial ports and V4L2 video devices
+    Probe,
+    /// Configuration tools
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CameraCommand {
+    /// Save frames from the camera as JPEG files
+    Snapshot {
+        /// Number of frames to save
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: u32,
+        /// Delay between frames in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+        /// Directory to write frames into
+        #[arg(short, long, default_value = ".")]
+        output_dir: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EspCommand {
+    /// Send one message (CMD:MOTOR:DIRECTION:STEPS) and wait for the reply
+    Send {
+        message: String,
+        /// Controller to talk to (default: the first in ESP32_CONTROLLERS)
+        #[arg(long)]
+        controller: Option<String>,
+    },
+    /// Interactive shell for sending messages to the ESP32
+    Repl {
+        /// Controller to talk to (default: the first in ESP32_CONTROLLERS)
+        #[arg(long)]
+        controller: Option<String>,
+    },
+    /// Replay a serial capture (see SERIAL_CAPTURE_DIR) against a simulated ESP32
+    Replay {
+        /// Capture file, e.g. captures/main.jsonl
+        file: String,
+        /// Playback speed factor (2 = twice as fast)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Load the configuration and report any problems
+    Check,
+}
+
+pub async fn dispatch(cli: Cli) -> anyhow::Result<()> {
+    let config = Config::load(cli.env_file.as_deref())?;
+    let log_handle = logging::init(&config.log)?;
+
+    match cli.command.unwrap_or(CliCommand::Run) {
+        CliCommand::Run => run::run(config, log_handle).await,
+        CliCommand::Camera(CameraCommand::Snapshot { count, interval_ms, output_dir }) => {
+            camera::snapshot(&config, count, interval_ms, &output_dir).await
+        }
+        CliCommand::Esp(EspCommand::Send { message, controller }) => {
+            esp::send(config.controller(controller.as_deref())?, &message).await
+        }
+        CliCommand::Esp(EspCommand::Repl { controller }) => esp::repl(config.controller(controller.as_deref())?).await,
+        CliCommand::Esp(EspCommand::Replay { file, speed }) => esp::replay(&file, speed).await,
+        CliCommand::Probe => probe::probe(),
+        CliCommand::Config(ConfigCommand::Check) => config::check(&config),
+    }
+}
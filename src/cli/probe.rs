@@ -0,0 +1,41 @@
+use tokio_serial::SerialPortType;
+
+/// Print the serial ports and V4L2 devices visible on this board.
+pub fn probe() -> anyhow::Result<()> {
+    println!("Serial ports:");
+    match tokio_serial::available_ports() {
+        Ok(ports) if ports.is_empty() => println!("  (none)"),
+        Ok(ports) => {
+            for port in ports {
+                match port.port_type {
+                    SerialPortType::UsbPort(usb) => println!(
+                        "  {}  usb {:04x}:{:04x}  serial={}  {} {}",
+                        port.port_name,
+                        usb.vid,
+                        usb.pid,
+                        usb.serial_number.as_deref().unwrap_or("-"),
+                        usb.manufacturer.as_deref().unwrap_or(""),
+                        usb.product.as_deref().unwrap_or(""),
+                    ),
+                    other => println!("  {}  {:?}", port.port_name, other),
+                }
+            }
+        }
+        Err(e) => eprintln!("  Failed to enumerate serial ports: {}", e),
+    }
+
+    println!("Video devices:");
+    let devices = v4l::context::enum_devices();
+    if devices.is_empty() {
+        println!("  (none)");
+    }
+    for dev in devices {
+        println!(
+            "  index {}  {}  {}",
+            dev.index(),
+            dev.path().display(),
+            dev.name().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use crate::backend::connection::connect_wss;
+use crate::backend::listener::run_listener;
+use crate::backend::processor::Processor;
+use crate::backend::session_state::SessionState;
+use crate::config::Config;
+use crate::controllers::camera::Camera;
+use crate::esp32::Controllers;
+use crate::logging::{self, LogHandle};
+use crate::monitoring::{self, HEALTH, METRICS};
+use crate::motion::Stage;
+
+use anyhow::Context;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{error, info};
+
+/// The device daemon: backend connection, camera capture and ESP32 control.
+pub async fn run(config: Config, log_handle: LogHandle) -> anyhow::Result<()> {
+    info!("Orange Pi Device Started...");
+    logging::spawn_reload_on_sighup(log_handle, config.env_file.clone());
+
+    // --- Shared state & queue ---
+    let session_state = Arc::new(RwLock::new(SessionState::new()));
+
+    // Serve metrics/health before connecting so a device stuck offline is still visible
+    if let Some(addr) = config.monitoring.addr {
+        let monitor_state = Arc::clone(&session_state);
+        tokio::spawn(async move {
+            if let Err(e) = monitoring::server::serve(addr, monitor_state).await {
+                error!(%addr, error = %e, "monitoring server stopped");
+            }
+        });
+    }
+
+    // 1️⃣ Connect to backend
+    let url = config.backend_url()?;
+    info!(host = config.backend.host.as_deref(), "connecting to backend");
+
+    // let url = "ws://127.0.0.1:9001"; // Local testing
+    let ws_stream = connect_wss(&url).await.context("Connection failed")?;
+    METRICS.ws_connected.set(1);
+    HEALTH.set_backend_connected(true);
+
+    // --- Split WebSocket ---
+    let (write, read) = ws_stream.split();
+
+    // session_state.write().await.connected = true; // set to StartStreaming
+
+    let (tx, rx) = mpsc::channel(100);
+
+    let mut camera = Camera::new(config.camera.index, config.camera.width, config.camera.height);
+    camera.spawn_task(Arc::clone(&session_state));
+
+    // Keeps running without the ESP32s; the processor reconnects once they are plugged in
+    let esp = Controllers::open(&config.controllers).await;
+    let estop = esp.emergency_stop();
+    let stage = Stage::load(&config.motion.state_path);
+
+    // --- Spawn listener & processor ---
+    let listener_state = Arc::clone(&session_state);
+    tokio::spawn(async move {
+        run_listener(read, tx, listener_state, estop).await;
+    });
+
+    let latest_frame = camera.latest_frame();
+    let processor_state = Arc::clone(&session_state);
+    tokio::spawn(async move {
+        let mut processor = Processor::new(rx, write, processor_state, latest_frame, esp, stage, config.motion);
+        processor.run().await;
+    });
+
+    // --- Main loop: systemd readiness, watchdog and status ---
+    monitoring::systemd::supervise(config.camera.index).await
+}
@@ -0,0 +1,469 @@
+use anyhow::Context;
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::backend::models::{Axis, Sense};
+use crate::esp32::FirmwareVersion;
+use crate::motion::backlash::{Backlash, BacklashMode};
+use crate::motion::calibration::{AxisCalibration, Calibration};
+use crate::motion::limits::{AxisLimits, LimitMode, SoftLimits};
+
+/// Device configuration, loaded from the environment (and `.env` if present).
+///
+/// Shared by every subcommand so the daemon and the hardware tools always
+/// agree on which serial port, camera and backend they talk to.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Env file given on the command line, if any (re-read on SIGHUP)
+    pub env_file: Option<String>,
+    pub backend: BackendConfig,
+    /// ESP32 boards, the first one being the default route for every axis
+    pub controllers: Vec<SerialConfig>,
+    pub camera: CameraConfig,
+    pub log: LogConfig,
+    pub monitoring: MonitoringConfig,
+    pub motion: MotionConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackendConfig {
+    pub host: Option<String>,
+    pub port: Option<String>,
+    pub device_name: Option<String>,
+    pub auth_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SerialConfig {
+    /// Controller name used for routing and in reports, e.g. `main`
+    pub name: String,
+    /// Used when no USB match is configured
+    pub port: String,
+    pub baud_rate: u32,
+    /// Find the ESP32 by USB identity instead of a fixed device path
+    pub usb: Option<UsbMatch>,
+    /// Oldest ESP32 firmware the daemon will drive
+    pub min_firmware: FirmwareVersion,
+    /// Ping the ESP32 when nothing has been exchanged for this long
+    pub ping_interval: Duration,
+    /// Missed pings in a row before the link is considered lost
+    pub ping_failures: u32,
+    /// Reset the board through DTR/RTS when the link is lost
+    pub reset_on_loss: bool,
+    /// Record all serial traffic; `None` disables capture
+    pub capture: Option<CaptureConfig>,
+}
+
+/// Where raw serial traffic is recorded, one file per controller.
+#[derive(Debug, Clone)]
+pub struct CaptureConfig {
+    pub dir: PathBuf,
+    /// A file is rotated once it would grow past this size
+    pub max_bytes: u64,
+    /// Rotated files kept besides the current one
+    pub files: u32,
+}
+
+/// USB identity of the ESP32's serial adapter; unset fields match anything.
+#[derive(Debug, Clone)]
+pub struct UsbMatch {
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CameraConfig {
+    pub index: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// `EnvFilter` directives, e.g. `info,orangepi_IA::esp32=debug`
+    pub filter: String,
+    pub format: LogFormat,
+}
+
+/// Limits applied to `Move` / `Zoom` requests from the backend.
+#[derive(Debug, Clone)]
+pub struct MotionConfig {
+    /// Steps used when a command does not specify any
+    pub default_steps: u32,
+    pub max_steps: u32,
+    /// Steps per second
+    pub max_speed: u32,
+    /// Steps per second squared
+    pub max_accel: u32,
+    /// Where the absolute stage position is persisted
+    pub state_path: PathBuf,
+    /// How long the ESP32 may take to home one axis
+    pub homing_timeout: Duration,
+    /// How long the ESP32 may take to finish one relative move
+    pub move_timeout: Duration,
+    /// Jog speed when `JogStart` does not give one, in steps per second
+    pub jog_default_speed: u32,
+    /// Longest gap between jog keepalives before the jog is stopped
+    pub jog_timeout: Duration,
+    /// Acceleration for `SetVelocity`, in steps per second squared
+    pub velocity_accel: f64,
+    /// Velocity decays to zero when no `SetVelocity` arrives for this long
+    pub velocity_timeout: Duration,
+    pub limits: SoftLimits,
+    pub calibration: Calibration,
+    pub backlash: Backlash,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitoringConfig {
+    /// Address of the local metrics/health HTTP server; `None` disables it
+    pub addr: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Config {
+    /// Load `.env` (or `env_file` when given) and read the configuration.
+    /// Only malformed values are errors here; missing backend settings are
+    /// reported by `backend_url` / `problems` so hardware tools still work.
+    pub fn load(env_file: Option<&str>) -> anyhow::Result<Self> {
+        match env_file {
+            Some(path) => {
+                dotenv::from_path(path).with_context(|| format!("loading env file {}", path))?;
+            }
+            None => {
+                dotenv::dotenv().ok();
+            }
+        }
+
+        let controllers = controllers()?;
+
+        Ok(Self {
+            env_file: env_file.map(str::to_string),
+            backend: BackendConfig {
+                host: optional("SERVER_HOST"),
+                port: optional("SERVER_PORT"),
+                device_name: optional("DEVICE_NAME"),
+                auth_token: optional("AUTH_TOKEN"),
+            },
+            controllers: controllers.clone(),
+            camera: CameraConfig {
+                index: parsed("CAMERA_INDEX", 20)?,
+                width: parsed("CAMERA_WIDTH", 640)?,
+                height: parsed("CAMERA_HEIGHT", 480)?,
+            },
+            log: LogConfig {
+                filter: optional("RUST_LOG")
+                    .or_else(|| optional("LOG_LEVEL"))
+                    .unwrap_or_else(|| "info".to_string()),
+                format: match optional("LOG_FORMAT").as_deref().map(str::to_ascii_lowercase).as_deref() {
+                    None | Some("text") => LogFormat::Text,
+                    Some("json") => LogFormat::Json,
+                    Some(other) => anyhow::bail!("LOG_FORMAT has invalid value '{}' (expected text or json)", other),
+                },
+            },
+            monitoring: MonitoringConfig {
+                addr: match optional("MONITOR_ADDR").as_deref() {
+                    Some("off") => None,
+                    Some(addr) => Some(addr.parse().with_context(|| format!("MONITOR_ADDR has invalid value '{}'", addr))?),
+                    None => Some(SocketAddr::from(([127, 0, 0, 1], 9100))),
+                },
+            },
+            motion: MotionConfig {
+                default_steps: parsed("MOVE_DEFAULT_STEPS", 5)?,
+                max_steps: parsed("MOVE_MAX_STEPS", 2000)?,
+                max_speed: parsed("MOVE_MAX_SPEED", 2000)?,
+                max_accel: parsed("MOVE_MAX_ACCEL", 20000)?,
+                state_path: PathBuf::from(
+                    optional("STAGE_STATE_PATH").unwrap_or_else(|| "stage_state.json".to_string()),
+                ),
+                homing_timeout: Duration::from_secs(parsed("HOMING_TIMEOUT_SECS", 30)?),
+                move_timeout: Duration::from_secs(parsed("MOVE_TIMEOUT_SECS", 30)?),
+                jog_default_speed: parsed("JOG_DEFAULT_SPEED", 500)?,
+                jog_timeout: Duration::from_millis(parsed("JOG_TIMEOUT_MS", 500)?),
+                velocity_accel: parsed("VELOCITY_ACCEL", 4000.0)?,
+                velocity_timeout: Duration::from_millis(parsed("VELOCITY_TIMEOUT_MS", 300)?),
+                limits: SoftLimits {
+                    x: axis_limits("X")?,
+                    y: axis_limits("Y")?,
+                    z: axis_limits("Z")?,
+                    z_objective_max: parsed_optional("Z_OBJECTIVE_LIMIT")?,
+                    mode: match optional("SOFT_LIMIT_MODE").as_deref().map(str::to_ascii_lowercase).as_deref() {
+                        None | Some("clamp") => LimitMode::Clamp,
+                        Some("reject") => LimitMode::Reject,
+                        Some(other) => anyhow::bail!("SOFT_LIMIT_MODE has invalid value '{}' (expected clamp or reject)", other),
+                    },
+                },
+                calibration: Calibration {
+                    x: axis_calibration("X", 2, &controllers)?,
+                    y: axis_calibration("Y", 1, &controllers)?,
+                    z: axis_calibration("Z", 3, &controllers)?,
+                    buttons: controller_index("BUTTONS_CONTROLLER", &controllers)?,
+                    sensors: controller_index("SENSORS_CONTROLLER", &controllers)?,
+                },
+                backlash: Backlash {
+                    x: parsed("BACKLASH_X_STEPS", 0)?,
+                    y: parsed("BACKLASH_Y_STEPS", 0)?,
+                    z: parsed("BACKLASH_Z_STEPS", 0)?,
+                    mode: match optional("BACKLASH_MODE").as_deref().map(str::to_ascii_lowercase).as_deref() {
+                        None | Some("takeup") => BacklashMode::TakeUp,
+                        Some("approach_forward") => BacklashMode::Approach(Sense::Forward),
+                        Some("approach_backward") => BacklashMode::Approach(Sense::Backward),
+                        Some(other) => anyhow::bail!(
+                            "BACKLASH_MODE has invalid value '{}' (expected takeup, approach_forward or approach_backward)",
+                            other
+                        ),
+                    },
+                },
+            },
+        })
+    }
+
+    /// Build the backend WebSocket URL, failing if any backend setting is missing.
+    pub fn backend_url(&self) -> anyhow::Result<String> {
+        let b = &self.backend;
+        let host = b.host.as_deref().context("SERVER_HOST not set")?;
+        let port = b.port.as_deref().context("SERVER_PORT not set")?;
+        let name = b.device_name.as_deref().context("DEVICE_NAME not set")?;
+        let token = b.auth_token.as_deref().context("AUTH_TOKEN not set")?;
+
+        Ok(format!(
+            "wss://{host}:{port}/orangepi/connect?device_name={name}&auth_token={token}",
+        ))
+    }
+
+    /// Controller by name, or the first one when no name is given.
+    pub fn controller(&self, name: Option<&str>) -> anyhow::Result<&SerialConfig> {
+        match name {
+            None => Ok(&self.controllers[0]),
+            Some(name) => self
+                .controllers
+                .iter()
+                .find(|c| c.name == name)
+                .with_context(|| format!("no controller named '{}' in ESP32_CONTROLLERS", name)),
+        }
+    }
+
+    /// List everything that would stop the daemon from starting.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let b = &self.backend;
+        for (key, value) in [
+            ("SERVER_HOST", &b.host),
+            ("SERVER_PORT", &b.port),
+            ("DEVICE_NAME", &b.device_name),
+            ("AUTH_TOKEN", &b.auth_token),
+        ] {
+            if value.is_none() {
+                problems.push(format!("{} not set", key));
+            }
+        }
+        if let Some(port) = &b.port
+            && port.parse::<u16>().is_err()
+        {
+            problems.push(format!("SERVER_PORT '{}' is not a valid port", port));
+        }
+        if self.controllers.iter().any(|c| c.ping_failures == 0) {
+            problems.push("ESP32_PING_FAILURES must be at least 1".to_string());
+        }
+        for (i, a) in self.controllers.iter().enumerate() {
+            for b in &self.controllers[i + 1..] {
+                if a.name == b.name {
+                    problems.push(format!("ESP32_CONTROLLERS lists '{}' twice", a.name));
+                } else if a.usb.is_none() && b.usb.is_none() && a.port == b.port {
+                    problems.push(format!("controllers '{}' and '{}' both use {}", a.name, b.name, a.port));
+                }
+            }
+        }
+        let m = &self.motion;
+        if m.default_steps == 0 || m.default_steps > m.max_steps {
+            problems.push(format!(
+                "MOVE_DEFAULT_STEPS {} must be between 1 and MOVE_MAX_STEPS ({})",
+                m.default_steps, m.max_steps
+            ));
+        }
+        for (name, limits) in [("X", &m.limits.x), ("Y", &m.limits.y), ("Z", &m.limits.z)] {
+            if let (Some(min), Some(max)) = (limits.min, limits.max)
+                && min >= max
+            {
+                problems.push(format!("SOFT_LIMIT_{name}_MIN ({min}) must be below SOFT_LIMIT_{name}_MAX ({max})"));
+            }
+        }
+        if m.jog_default_speed == 0 || m.jog_default_speed > m.max_speed {
+            problems.push(format!(
+                "JOG_DEFAULT_SPEED {} must be between 1 and MOVE_MAX_SPEED ({})",
+                m.jog_default_speed, m.max_speed
+            ));
+        }
+        if !(m.velocity_accel.is_finite() && m.velocity_accel > 0.0) {
+            problems.push(format!("VELOCITY_ACCEL ({}) must be positive", m.velocity_accel));
+        }
+        let cal = &m.calibration;
+        for (name, axis) in [("X", &cal.x), ("Y", &cal.y), ("Z", &cal.z)] {
+            if !(axis.steps_per_um.is_finite() && axis.steps_per_um > 0.0) {
+                problems.push(format!("AXIS_{name}_STEPS_PER_UM ({}) must be positive", axis.steps_per_um));
+            }
+            if let Some(speed) = axis.speed.filter(|&s| s == 0 || s > m.max_speed) {
+                problems.push(format!("AXIS_{name}_SPEED {} must be between 1 and MOVE_MAX_SPEED ({})", speed, m.max_speed));
+            }
+            if let Some(accel) = axis.accel.filter(|&a| a == 0 || a > m.max_accel) {
+                problems.push(format!("AXIS_{name}_ACCEL {} must be between 1 and MOVE_MAX_ACCEL ({})", accel, m.max_accel));
+            }
+        }
+        let (x, y, z) = (cal.route(Axis::X), cal.route(Axis::Y), cal.route(Axis::Z));
+        if x == y || x == z || y == z {
+            problems.push(format!(
+                "AXIS_X/Y/Z_MOTOR must be distinct on the same controller (got {}, {}, {})",
+                cal.x.motor, cal.y.motor, cal.z.motor
+            ));
+        }
+        if self.camera.width <= 0 || self.camera.height <= 0 {
+            problems.push(format!(
+                "camera resolution {}x{} is invalid",
+                self.camera.width, self.camera.height
+            ));
+        }
+        problems
+    }
+}
+
+impl LogConfig {
+    /// Read the log filter straight from the env file, ignoring values already
+    /// in the process environment (which `dotenv` never overwrites).
+    #[allow(deprecated)] // the iter API is the only way to read without mutating the env
+    pub fn reread_filter(env_file: Option<&str>) -> anyhow::Result<String> {
+        let iter = match env_file {
+            Some(path) => dotenv::from_path_iter(path)?,
+            None => dotenv::dotenv_iter()?,
+        };
+        let mut rust_log = None;
+        let mut log_level = None;
+        for item in iter {
+            let (key, value) = item?;
+            match key.as_str() {
+                "RUST_LOG" => rust_log = Some(value),
+                "LOG_LEVEL" => log_level = Some(value),
+                _ => {}
+            }
+        }
+        rust_log
+            .or(log_level)
+            .context("neither RUST_LOG nor LOG_LEVEL set in env file")
+    }
+}
+
+fn optional(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn parsed<T>(key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    Ok(parsed_optional(key)?.unwrap_or(default))
+}
+
+fn parsed_optional<T>(key: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    optional(key)
+        .map(|raw| {
+            raw.trim()
+                .parse()
+                .map_err(|e| anyhow::anyhow!("{} has invalid value '{}': {}", key, raw, e))
+        })
+        .transpose()
+}
+
+fn hex_optional(key: &str) -> anyhow::Result<Option<u16>> {
+    optional(key)
+        .map(|raw| {
+            let digits = raw.trim();
+            let digits = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")).unwrap_or(digits);
+            u16::from_str_radix(digits, 16)
+                .map_err(|e| anyhow::anyhow!("{} has invalid value '{}': {}", key, raw, e))
+        })
+        .transpose()
+}
+
+fn usb_match(prefix: &str) -> anyhow::Result<Option<UsbMatch>> {
+    let usb = UsbMatch {
+        vid: hex_optional(&format!("{}_USB_VID", prefix))?,
+        pid: hex_optional(&format!("{}_USB_PID", prefix))?,
+        serial: optional(&format!("{}_USB_SERIAL", prefix)),
+    };
+    Ok((usb.vid.is_some() || usb.pid.is_some() || usb.serial.is_some()).then_some(usb))
+}
+
+/// Boards named in `ESP32_CONTROLLERS` (default `main`). The first reads the
+/// plain `SERIAL_*` settings, later ones `SERIAL_<NAME>_*`; the firmware and
+/// watchdog settings apply to all of them.
+fn controllers() -> anyhow::Result<Vec<SerialConfig>> {
+    let names = optional("ESP32_CONTROLLERS").unwrap_or_else(|| "main".to_string());
+    let mut controllers = Vec::new();
+    for (i, name) in names.split(',').map(str::trim).filter(|n| !n.is_empty()).enumerate() {
+        let prefix = if i == 0 { "SERIAL".to_string() } else { format!("SERIAL_{}", name.to_ascii_uppercase()) };
+        controllers.push(SerialConfig {
+            name: name.to_string(),
+            port: optional(&format!("{}_PORT", prefix)).unwrap_or_else(|| "/dev/ttyUSB0".to_string()),
+            baud_rate: parsed(&format!("{}_BAUD", prefix), 115200)?,
+            usb: usb_match(&prefix)?,
+            min_firmware: parsed("ESP32_MIN_FIRMWARE", FirmwareVersion { major: 1, minor: 0, patch: 0 })?,
+            ping_interval: Duration::from_millis(parsed("ESP32_PING_INTERVAL_MS", 2000)?),
+            ping_failures: parsed("ESP32_PING_FAILURES", 3)?,
+            reset_on_loss: parsed("ESP32_RESET_ON_LOSS", true)?,
+            capture: match optional("SERIAL_CAPTURE_DIR") {
+                Some(dir) => Some(CaptureConfig {
+                    dir: PathBuf::from(dir),
+                    max_bytes: parsed::<u64>("SERIAL_CAPTURE_MAX_KB", 1024)? * 1024,
+                    files: parsed("SERIAL_CAPTURE_FILES", 5)?,
+                }),
+                None => None,
+            },
+        });
+    }
+    if controllers.is_empty() {
+        anyhow::bail!("ESP32_CONTROLLERS names no controller");
+    }
+    Ok(controllers)
+}
+
+/// Index of the controller `key` names, the first one when unset.
+fn controller_index(key: &str, controllers: &[SerialConfig]) -> anyhow::Result<usize> {
+    match optional(key) {
+        None => Ok(0),
+        Some(name) => controllers
+            .iter()
+            .position(|c| c.name == name.trim())
+            .with_context(|| format!("{} names '{}', which is not in ESP32_CONTROLLERS", key, name)),
+    }
+}
+
+fn axis_calibration(axis: &str, default_motor: u8, controllers: &[SerialConfig]) -> anyhow::Result<AxisCalibration> {
+    Ok(AxisCalibration {
+        controller: controller_index(&format!("AXIS_{}_CONTROLLER", axis), controllers)?,
+        motor: parsed(&format!("AXIS_{}_MOTOR", axis), default_motor)?,
+        steps_per_um: parsed(&format!("AXIS_{}_STEPS_PER_UM", axis), 1.0)?,
+        invert: parsed(&format!("AXIS_{}_INVERT", axis), false)?,
+        speed: parsed_optional(&format!("AXIS_{}_SPEED", axis))?,
+        accel: parsed_optional(&format!("AXIS_{}_ACCEL", axis))?,
+    })
+}
+
+fn axis_limits(axis: &str) -> anyhow::Result<AxisLimits> {
+    Ok(AxisLimits {
+        min: parsed_optional(&format!("SOFT_LIMIT_{}_MIN", axis))?,
+        max: parsed_optional(&format!("SOFT_LIMIT_{}_MAX", axis))?,
+    })
+}
@@ -7,11 +7,16 @@ use opencv::{
 
 use tokio::sync::RwLock; 
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, Duration, Instant};
 use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
 use crate::backend::session_state::SessionState;
+use crate::logging::RateLimit;
+use crate::monitoring::health::Task;
+use crate::monitoring::{HEALTH, METRICS};
 
 pub struct Camera {
+    index: i32,
     width: i32,
     height: i32,
     latest_frame: Arc<RwLock<Vec<u8>>>, 
@@ -19,8 +24,9 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(width: i32, height: i32) -> Self {
+    pub fn new(index: i32, width: i32, height: i32) -> Self {
         Self {
+            index,
             width,
             height,
             latest_frame: Arc::new(RwLock::new(Vec::new())),
@@ -30,9 +36,10 @@ impl Camera {
 
     /// Spawn the capture task based on session_state
     pub fn spawn_task(&mut self, session_state: Arc<RwLock<SessionState>>) {
-        println!("[Camera] Capture task started...");
+        info!("capture task started");
         let latest_frame = Arc::clone(&self.latest_frame);
         let cancel = self.cancel_token.clone();
+        let index = self.index;
         let width = self.width;
         let height = self.height;
 
@@ -40,15 +47,16 @@ impl Camera {
 
             loop {
                 if cancel.is_cancelled() { break; }
+                HEALTH.tick(Task::Camera);
 
                 let connected = { session_state.read().await.connected };
 
                 if connected {
                     // Open the camera only when connected
-                    let mut capture = match videoio::VideoCapture::new(20, videoio::CAP_V4L2) {
-                        Ok(cap) => {println!("Camera opened"); cap},
+                    let mut capture = match videoio::VideoCapture::new(index, videoio::CAP_V4L2) {
+                        Ok(cap) => {info!(index, "camera opened"); HEALTH.set_camera_opened(true); cap},
                         Err(e) => {
-                            eprintln!("Failed to open camera: {}", e);
+                            warn!(index, error = %e, "failed to open camera");
                             sleep(Duration::from_millis(500)).await;
                             continue;
                         }
@@ -57,34 +65,76 @@ impl Camera {
                     capture.set(videoio::CAP_PROP_FRAME_HEIGHT, height as f64).ok();
 
                     // Capture loop while connected
+                    let mut frame_log = RateLimit::new(Duration::from_secs(5));
+                    let mut fps_window = Instant::now();
+                    let mut fps_frames = 0u32;
                     while session_state.read().await.connected && !cancel.is_cancelled() {
+                        HEALTH.tick(Task::Camera);
                         let mut frame = core::Mat::default();
                         if let Ok(read_ok) = capture.read(&mut frame) {
                             if read_ok && !frame.empty() {
                                 // Encode asynchronously (optional, see previous optimization)
                                 let mut buf = core::Vector::<u8>::new();
                                 let params = core::Vector::<i32>::new();
-                                if let Ok(_) = imgcodecs::imencode(".jpg", &frame, &mut buf, &params) {
+                                let encode_timer = METRICS.jpeg_encode_seconds.start_timer();
+                                let encoded = imgcodecs::imencode(".jpg", &frame, &mut buf, &params);
+                                encode_timer.observe_duration();
+                                if encoded.is_ok() {
+                                    METRICS.camera_frames.inc();
+                                    HEALTH.mark_frame();
+                                    fps_frames += 1;
+                                    if let Some(skipped) = frame_log.check() {
+                                        debug!(bytes = buf.len(), skipped, "captured frame");
+                                    }
                                     let mut shared = latest_frame.write().await;
                                     *shared = buf.to_vec();
                                 }
+                            } else {
+                                METRICS.camera_read_failures.inc();
                             }
+                        } else {
+                            METRICS.camera_read_failures.inc();
+                        }
+
+                        let elapsed = fps_window.elapsed();
+                        if elapsed >= Duration::from_secs(1) {
+                            METRICS.camera_fps.set(fps_frames as f64 / elapsed.as_secs_f64());
+                            fps_window = Instant::now();
+                            fps_frames = 0;
                         }
                         sleep(Duration::from_millis(1)).await; // reduce CPU usage
                     }
 
                     // Drop capture when disconnected
                     drop(capture);
-                    println!("[Camera] Camera closed due to disconnect.");
+                    METRICS.camera_fps.set(0.0);
+                    HEALTH.set_camera_opened(false);
+                    info!("camera closed due to disconnect");
                 } else {
                     sleep(Duration::from_millis(50)).await; // less aggressive idle sleep
                 }
             }
-            println!("[Camera] Capture task stopped.");
+            info!("capture task stopped");
         });
     }
 
     pub fn latest_frame(&self) -> Arc<RwLock<Vec<u8>>> {
         Arc::clone(&self.latest_frame)
     }
+
+    /// Check that the camera device can be opened (used before a session starts).
+    pub fn is_available(index: i32) -> bool {
+        match videoio::VideoCapture::new(index, videoio::CAP_V4L2) {
+            Ok(capture) => capture.is_opened().unwrap_or(false),
+            Err(e) => {
+                debug!(index, error = %e, "camera probe failed");
+                false
+            }
+        }
+    }
+
+    /// Stop the capture task and release the camera
+    pub fn stop(&self) {
+        self.cancel_token.cancel();
+    }
 }
@@ -0,0 +1,304 @@
+// Recording of raw serial traffic, one JSON object per line:
+//
+//   {"ts_us":1760000000000000,"dir":"tx","hex":"4031...0a","text":"@1|7|MOVE:2:FWD:100|3F2A\n"}
+//
+// <ts_us> is the wall clock in microseconds, <dir> is tx (host to ESP32) or rx,
+// <hex> the bytes exactly as read or written and <text> the same bytes decoded
+// lossily, for reading the file by eye. One record per read or write call, so
+// a line may be split across records.
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{ready, Context, Poll};
+use std::thread;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::{error, info};
+
+use crate::config::CaptureConfig;
+use crate::monitoring::METRICS;
+
+/// Records waiting for the capture file. When the disk falls this far behind,
+/// new records are dropped (and counted) rather than held in memory.
+const QUEUE_RECORDS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Host to ESP32
+    Tx,
+    /// ESP32 to host
+    Rx,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub ts_us: i64,
+    pub dir: Direction,
+    pub hex: String,
+    pub text: String,
+}
+
+impl Record {
+    fn new(dir: Direction, bytes: &[u8]) -> Self {
+        Self {
+            ts_us: chrono::Utc::now().timestamp_micros(),
+            dir,
+            hex: bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+            text: String::from_utf8_lossy(bytes).into_owned(),
+        }
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        if !self.hex.len().is_multiple_of(2) {
+            return Err(format!("odd number of hex digits in '{}'", self.hex));
+        }
+        (0..self.hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&self.hex[i..i + 2], 16).map_err(|e| format!("'{}': {}", self.hex, e)))
+            .collect()
+    }
+}
+
+/// Read a capture file back, oldest record first.
+pub fn read(path: &Path) -> anyhow::Result<Vec<Record>> {
+    let file = File::open(path).map_err(|e| anyhow::anyhow!("opening {}: {}", path.display(), e))?;
+    let mut records = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| anyhow::anyhow!("{} line {}: {}", path.display(), n + 1, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Handle for recording traffic; a background thread does the file I/O, so
+/// recording never blocks the serial tasks.
+#[derive(Clone)]
+pub struct Capture {
+    records: mpsc::SyncSender<Record>,
+}
+
+impl Capture {
+    /// Start writing to `<dir>/<controller>.jsonl`, rotated by size.
+    pub fn start(config: &CaptureConfig, controller: &str) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let mut file = RotatingFile::open(
+            config.dir.join(format!("{}.jsonl", controller)),
+            config.max_bytes,
+            config.files,
+        )?;
+        info!(path = %file.path.display(), "capturing serial traffic");
+
+        let (records, rx) = mpsc::sync_channel::<Record>(QUEUE_RECORDS);
+        thread::spawn(move || {
+            // Ends once every handle (and every port using one) is dropped
+            for record in rx {
+                if let Err(e) = file.write(&record) {
+                    error!(path = %file.path.display(), error = %e, "serial capture stopped");
+                    break;
+                }
+            }
+        });
+        Ok(Self { records })
+    }
+
+    fn record(&self, dir: Direction, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        // Disconnected only once the writer thread gave up, which it has already logged
+        if let Err(mpsc::TrySendError::Full(_)) = self.records.try_send(Record::new(dir, bytes)) {
+            METRICS.serial_capture_dropped.inc();
+        }
+    }
+}
+
+/// Size-limited log file keeping `files` older generations as `<path>.1`, `<path>.2`, ...
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    files: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, files: u32) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self { path, max_bytes, files, file, written })
+    }
+
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+        line.push('\n');
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        // Unbuffered, so a capture survives the process being killed
+        self.file.write_all(line.as_bytes())?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let generation = |n: u32| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        if self.files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.files).rev() {
+                if generation(n).exists() {
+                    fs::rename(generation(n), generation(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, generation(1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// Port wrapper passing every byte read or written to a `Capture`.
+pub struct Recorded<T> {
+    inner: T,
+    capture: Capture,
+}
+
+impl<T> Recorded<T> {
+    pub fn new(inner: T, capture: Capture) -> Self {
+        Self { inner, capture }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Recorded<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.capture.record(Direction::Rx, &buf.filled()[before..]);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Recorded<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, data))?;
+        self.capture.record(Direction::Tx, &data[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty directory of its own for one test.
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bsmanager-capture-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn generation(path: &Path, n: u32) -> PathBuf {
+        PathBuf::from(format!("{}.{}", path.display(), n))
+    }
+
+    fn line_count(path: &Path) -> usize {
+        fs::read_to_string(path).map_or(0, |text| text.lines().count())
+    }
+
+    /// A record serialized to exactly one line of `RECORD_LEN` bytes.
+    fn record(byte: u8) -> Record {
+        Record { ts_us: 1, dir: Direction::Tx, hex: format!("{:02x}", byte), text: "x".to_string() }
+    }
+
+    fn record_len() -> u64 {
+        serde_json::to_string(&record(0)).unwrap().len() as u64 + 1
+    }
+
+    #[test]
+    fn rotates_when_the_next_record_would_not_fit() {
+        let dir = scratch_dir("rotate");
+        let path = dir.join("main.jsonl");
+        let mut file = RotatingFile::open(path.clone(), 2 * record_len(), 2).unwrap();
+        for byte in 0..5 {
+            file.write(&record(byte)).unwrap();
+        }
+        assert_eq!(line_count(&path), 1);
+        assert_eq!(line_count(&generation(&path, 1)), 2);
+        assert_eq!(line_count(&generation(&path, 2)), 2);
+        assert!(!generation(&path, 3).exists());
+
+        // The oldest generation falls off the end
+        file.write(&record(5)).unwrap();
+        file.write(&record(6)).unwrap();
+        let newest_rotated = read(&generation(&path, 1)).unwrap();
+        assert_eq!(newest_rotated.iter().map(|r| r.hex.as_str()).collect::<Vec<_>>(), ["04", "05"]);
+        let oldest_kept = read(&generation(&path, 2)).unwrap();
+        assert_eq!(oldest_kept.iter().map(|r| r.hex.as_str()).collect::<Vec<_>>(), ["02", "03"]);
+        assert!(!generation(&path, 3).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn without_old_files_rotation_starts_over() {
+        let dir = scratch_dir("no-generations");
+        let path = dir.join("main.jsonl");
+        let mut file = RotatingFile::open(path.clone(), record_len(), 0).unwrap();
+        for byte in 0..3 {
+            file.write(&record(byte)).unwrap();
+        }
+        assert_eq!(read(&path).unwrap().len(), 1);
+        assert!(!generation(&path, 1).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_record_larger_than_the_limit_is_still_written() {
+        let dir = scratch_dir("oversized");
+        let path = dir.join("main.jsonl");
+        let mut file = RotatingFile::open(path.clone(), 1, 1).unwrap();
+        file.write(&record(0)).unwrap();
+        assert_eq!(line_count(&path), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reopening_continues_the_current_file() {
+        let dir = scratch_dir("reopen");
+        let path = dir.join("main.jsonl");
+        RotatingFile::open(path.clone(), 2 * record_len(), 1).unwrap().write(&record(0)).unwrap();
+        let mut file = RotatingFile::open(path.clone(), 2 * record_len(), 1).unwrap();
+        file.write(&record(1)).unwrap();
+        assert_eq!(line_count(&path), 2);
+        file.write(&record(2)).unwrap();
+        assert_eq!(line_count(&path), 1);
+        assert_eq!(line_count(&generation(&path, 1)), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn records_keep_the_exact_bytes() {
+        let bytes = b"@1|7|ACK:7|1234\r\n\xff";
+        let record = Record::new(Direction::Rx, bytes);
+        assert_eq!(record.bytes().unwrap(), bytes);
+        assert!(Record { hex: "abc".to_string(), ..record.clone() }.bytes().is_err());
+        assert!(Record { hex: "zz".to_string(), ..record }.bytes().is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use crate::config::SerialConfig;
+use crate::esp32::{EmergencyStop, EspEvent, EspHandler};
+
+/// Every ESP32 board of the device, addressed by its index in the configuration.
+pub struct Controllers {
+    handlers: Vec<EspHandler>,
+}
+
+impl Controllers {
+    /// Open every configured controller; any that is missing starts disconnected.
+    pub async fn open(configs: &[SerialConfig]) -> Self {
+        let mut handlers = Vec::with_capacity(configs.len());
+        for config in configs {
+            handlers.push(EspHandler::open(config.clone()).await);
+        }
+        Self { handlers }
+    }
+
+    /// Controllers already talking to their boards, e.g. fakes in tests.
+    #[cfg(test)]
+    pub fn from_handlers(handlers: Vec<EspHandler>) -> Self {
+        Self { handlers }
+    }
+
+    pub fn len(&self) -> usize {
+        self.handlers.len()
+    }
+
+    pub fn get(&self, controller: usize) -> &EspHandler {
+        &self.handlers[controller]
+    }
+
+    pub fn get_mut(&mut self, controller: usize) -> &mut EspHandler {
+        &mut self.handlers[controller]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EspHandler> {
+        self.handlers.iter()
+    }
+
+    /// Stops every controller at once.
+    pub fn emergency_stop(&self) -> EmergencyStop {
+        EmergencyStop::all(self.handlers.iter().map(EspHandler::emergency_stop))
+    }
+
+    /// Events from all controllers, tagged with the controller they came from.
+    pub fn take_events(&mut self) -> Vec<(usize, EspEvent)> {
+        self.handlers
+            .iter_mut()
+            .enumerate()
+            .flat_map(|(i, esp)| esp.take_events().into_iter().map(move |event| (i, event)))
+            .collect()
+    }
+}
@@ -0,0 +1,87 @@
+use tokio::io;
+use tokio::task;
+use tokio::time::{self, Duration};
+use tokio_serial::SerialPortType;
+use tracing::{debug, info};
+
+use crate::config::{SerialConfig, UsbMatch};
+use crate::esp32::capture::Capture;
+use crate::esp32::SerialHandler;
+
+/// Device path of the ESP32: the first USB port matching `config.usb`, or
+/// the fixed `config.port` when no USB identity is configured.
+pub fn locate(config: &SerialConfig) -> io::Result<String> {
+    let Some(usb) = &config.usb else {
+        return Ok(config.port.clone());
+    };
+    let ports = tokio_serial::available_ports().map_err(io::Error::from)?;
+    ports
+        .into_iter()
+        .find(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => {
+                let found = matches(usb, info);
+                debug!(port = port.port_name, vid = info.vid, pid = info.pid, found, "checking USB serial port");
+                found
+            }
+            _ => false,
+        })
+        .map(|port| port.port_name)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no serial port matches {}", describe(usb))))
+}
+
+/// Locate the ESP32 and open its port, recording its traffic to `capture` if given.
+///
+/// Enumerating USB devices and opening the port both block, so they run on
+/// the blocking pool rather than stalling the caller's loop.
+pub async fn open(config: &SerialConfig, capture: Option<Capture>) -> io::Result<(String, SerialHandler)> {
+    let config = config.clone();
+    task::spawn_blocking(move || {
+        let path = locate(&config)?;
+        let serial = SerialHandler::new(&path, config.baud_rate, capture)
+            .map_err(|e| io::Error::other(format!("{}: {}", path, e)))?;
+        Ok((path, serial))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Reboot the ESP32 through the USB adapter's modem lines, as esptool does:
+/// RTS drives the chip's EN pin and DTR its boot strap, held high for a normal boot.
+///
+/// The port must not be open elsewhere in the process.
+pub async fn reset(config: &SerialConfig) -> io::Result<()> {
+    let config = config.clone();
+    let (path, mut port) = task::spawn_blocking(move || {
+        let path = locate(&config)?;
+        let port = tokio_serial::new(&path, config.baud_rate).open().map_err(io::Error::from)?;
+        io::Result::Ok((path, port))
+    })
+    .await
+    .map_err(io::Error::other)??;
+    info!(port = path, "resetting ESP32");
+    port.write_data_terminal_ready(false).map_err(io::Error::from)?;
+    port.write_request_to_send(true).map_err(io::Error::from)?;
+    time::sleep(Duration::from_millis(100)).await;
+    port.write_request_to_send(false).map_err(io::Error::from)?;
+    Ok(())
+}
+
+fn matches(usb: &UsbMatch, info: &tokio_serial::UsbPortInfo) -> bool {
+    usb.vid.is_none_or(|vid| vid == info.vid)
+        && usb.pid.is_none_or(|pid| pid == info.pid)
+        && usb.serial.as_ref().is_none_or(|s| info.serial_number.as_ref() == Some(s))
+}
+
+fn describe(usb: &UsbMatch) -> String {
+    let mut parts = Vec::new();
+    if let Some(vid) = usb.vid {
+        parts.push(format!("vid {:04x}", vid));
+    }
+    if let Some(pid) = usb.pid {
+        parts.push(format!("pid {:04x}", pid));
+    }
+    if let Some(serial) = &usb.serial {
+        parts.push(format!("serial {}", serial));
+    }
+    parts.join(", ")
+}
@@ -0,0 +1,142 @@
+use crate::backend::models::Sense;
+use crate::esp32::{EspCommand, EspMessage};
+
+/// Message the ESP32 sends on its own rather than as a reply.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EspEvent {
+    /// A MOVE / HOME finished after `steps`
+    Done { motor: u8, steps: u32 },
+    /// A MOVE / HOME stopped early after `steps`
+    Failed { motor: u8, reason: String, steps: u32 },
+    /// A jog ended after `steps`
+    Jogged { motor: u8, sense: Sense, steps: u32 },
+    Limit { motor: u8, sense: Sense },
+    EmergencyStopped,
+    /// The driver detected a stall; the motor lost steps somewhere in the last move
+    Stalled { motor: u8 },
+    /// The driver reported a fault (overtemperature, short, ...) and disabled the motor
+    DriverFault { motor: u8, code: String },
+    Button { id: u8, pressed: bool },
+    Sensor { name: String, value: u32 },
+}
+
+impl EspEvent {
+    /// Motor the event is about, if any.
+    pub fn motor(&self) -> Option<u8> {
+        match self {
+            EspEvent::Done { motor, .. }
+            | EspEvent::Failed { motor, .. }
+            | EspEvent::Jogged { motor, .. }
+            | EspEvent::Limit { motor, .. }
+            | EspEvent::Stalled { motor }
+            | EspEvent::DriverFault { motor, .. } => Some(*motor),
+            EspEvent::EmergencyStopped | EspEvent::Button { .. } | EspEvent::Sensor { .. } => None,
+        }
+    }
+
+    /// Interpret a message, or `None` if it is not a (well-formed) event.
+    pub fn from_message(msg: &EspMessage) -> Option<EspEvent> {
+        let (motor, steps) = (msg.motor, msg.steps);
+        let direction = msg.direction();
+        Some(match msg.cmd {
+            EspCommand::Done => EspEvent::Done { motor, steps },
+            EspCommand::Fail => EspEvent::Failed { motor, reason: direction?.to_string(), steps },
+            EspCommand::Jogged => EspEvent::Jogged { motor, sense: Sense::from_esp(direction?)?, steps },
+            EspCommand::Limit => EspEvent::Limit { motor, sense: Sense::from_esp(direction?)? },
+            EspCommand::EStop => EspEvent::EmergencyStopped,
+            EspCommand::Stall => EspEvent::Stalled { motor },
+            EspCommand::Fault => EspEvent::DriverFault { motor, code: direction?.to_string() },
+            EspCommand::Button => EspEvent::Button {
+                id: motor,
+                pressed: match direction? {
+                    "PRESS" => true,
+                    "RELEASE" => false,
+                    _ => return None,
+                },
+            },
+            EspCommand::Sensor => EspEvent::Sensor { name: direction?.to_string(), value: steps },
+            // Host-to-ESP32 commands echoed back are not events
+            EspCommand::Move
+            | EspCommand::Home
+            | EspCommand::Jog
+            | EspCommand::JogStop
+            | EspCommand::Reset
+            | EspCommand::Set
+            | EspCommand::Info
+            | EspCommand::Ping => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(line: &str) -> Option<EspEvent> {
+        EspEvent::from_message(&line.parse().unwrap())
+    }
+
+    #[test]
+    fn reads_every_event() {
+        assert_eq!(event("DONE:1:FWD:20"), Some(EspEvent::Done { motor: 1, steps: 20 }));
+        assert_eq!(
+            event("FAIL:2:LIMIT:7"),
+            Some(EspEvent::Failed { motor: 2, reason: "LIMIT".to_string(), steps: 7 })
+        );
+        assert_eq!(
+            event("JOGGED:3:BWD:150"),
+            Some(EspEvent::Jogged { motor: 3, sense: Sense::Backward, steps: 150 })
+        );
+        assert_eq!(event("LIMIT:1:FWD:0"), Some(EspEvent::Limit { motor: 1, sense: Sense::Forward }));
+        assert_eq!(event("ESTOP:0::0"), Some(EspEvent::EmergencyStopped));
+        assert_eq!(event("STALL:2:FWD:0"), Some(EspEvent::Stalled { motor: 2 }));
+        assert_eq!(
+            event("FAULT:3:OVERTEMP:0"),
+            Some(EspEvent::DriverFault { motor: 3, code: "OVERTEMP".to_string() })
+        );
+        assert_eq!(event("BUTTON:4:PRESS:0"), Some(EspEvent::Button { id: 4, pressed: true }));
+        assert_eq!(event("BUTTON:4:RELEASE:0"), Some(EspEvent::Button { id: 4, pressed: false }));
+        assert_eq!(
+            event("SENSOR:0:temp:231"),
+            Some(EspEvent::Sensor { name: "temp".to_string(), value: 231 })
+        );
+    }
+
+    #[test]
+    fn rejects_events_missing_their_direction_field() {
+        for line in ["FAIL:2::7", "JOGGED:3::150", "LIMIT:1::0", "FAULT:3::0", "BUTTON:4::0", "SENSOR:0::231"] {
+            assert_eq!(event(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_senses_and_button_states() {
+        assert_eq!(event("LIMIT:1:UP:0"), None);
+        assert_eq!(event("JOGGED:1:SIDEWAYS:5"), None);
+        assert_eq!(event("BUTTON:4:HOLD:0"), None);
+    }
+
+    #[test]
+    fn host_commands_are_not_events() {
+        for cmd in [
+            EspCommand::Move,
+            EspCommand::Home,
+            EspCommand::Jog,
+            EspCommand::JogStop,
+            EspCommand::Reset,
+            EspCommand::Set,
+            EspCommand::Info,
+            EspCommand::Ping,
+        ] {
+            assert_eq!(EspEvent::from_message(&EspMessage::command(cmd)), None, "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn motor_is_reported_for_motor_events_only() {
+        assert_eq!(event("DONE:1:FWD:20").unwrap().motor(), Some(1));
+        assert_eq!(event("STALL:2:FWD:0").unwrap().motor(), Some(2));
+        assert_eq!(event("ESTOP:0::0").unwrap().motor(), None);
+        assert_eq!(event("BUTTON:4:PRESS:0").unwrap().motor(), None);
+    }
+}
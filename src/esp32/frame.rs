@@ -0,0 +1,140 @@
+// Framing around every line on the serial link:
+//
+//   @<version>|<seq>|<body>|<crc>
+//
+// <version> is 1, <seq> a decimal u16 chosen by the sender, <body> the message
+// (e.g. MOVE:2:FWD:100) and <crc> the CRC-16/CCITT-FALSE of everything between
+// '@' and the last '|', as four hex digits.
+//
+// Host commands are retried with the same <seq>, so the ESP32 can answer a
+// repeat without executing it twice. Replies carry the sequence they answer:
+// ACK:<seq> / ERR:<seq>. Events use the ESP32's own sequence counter; an
+// event repeating the sequence number of the one before it is a retransmission
+// and is dropped.
+use std::fmt;
+
+pub const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub seq: u16,
+    pub body: String,
+}
+
+/// Why a received line was discarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    /// No leading '@' (noise, or firmware without framing)
+    Unframed,
+    Version(String),
+    Malformed,
+    Crc { expected: u16, actual: u16 },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Unframed => f.write_str("line is not framed"),
+            FrameError::Version(v) => write!(f, "unsupported frame version '{}'", v),
+            FrameError::Malformed => f.write_str("malformed frame"),
+            FrameError::Crc { expected, actual } => {
+                write!(f, "CRC mismatch (frame says {:04X}, computed {:04X})", expected, actual)
+            }
+        }
+    }
+}
+
+pub fn encode(seq: u16, body: &str) -> String {
+    let inner = format!("{}|{}|{}", VERSION, seq, body);
+    format!("@{}|{:04X}", inner, crc16(inner.as_bytes()))
+}
+
+pub fn decode(line: &str) -> Result<Frame, FrameError> {
+    let inner = line.trim().strip_prefix('@').ok_or(FrameError::Unframed)?;
+    let (covered, crc) = inner.rsplit_once('|').ok_or(FrameError::Malformed)?;
+    let expected = u16::from_str_radix(crc, 16).map_err(|_| FrameError::Malformed)?;
+    let actual = crc16(covered.as_bytes());
+    if expected != actual {
+        return Err(FrameError::Crc { expected, actual });
+    }
+
+    let mut parts = covered.splitn(3, '|');
+    let (Some(version), Some(seq), Some(body)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(FrameError::Malformed);
+    };
+    if version != VERSION.to_string() {
+        return Err(FrameError::Version(version.to_string()));
+    }
+    let seq = seq.parse().map_err(|_| FrameError::Malformed)?;
+    Ok(Frame { seq, body: body.to_string() })
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc16_matches_the_ccitt_false_check_values() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(b""), 0xFFFF);
+        assert_eq!(crc16(b"A"), 0xB915);
+    }
+
+    #[test]
+    fn encode_covers_version_seq_and_body() {
+        let line = encode(7, "MOVE:2:FWD:100");
+        let crc = crc16(b"1|7|MOVE:2:FWD:100");
+        assert_eq!(line, format!("@1|7|MOVE:2:FWD:100|{:04X}", crc));
+    }
+
+    #[test]
+    fn decode_reverses_encode() {
+        for (seq, body) in [(0, "ACK:0"), (65535, "DONE:1:FWD:20"), (42, "SENSOR:0:temp|x:3"), (1, "")] {
+            assert_eq!(decode(&encode(seq, body)), Ok(Frame { seq, body: body.to_string() }));
+        }
+    }
+
+    #[test]
+    fn decode_ignores_surrounding_whitespace() {
+        let line = format!("  {}\r\n", encode(3, "PING:0::0"));
+        assert_eq!(decode(&line).unwrap().body, "PING:0::0");
+    }
+
+    #[test]
+    fn decode_rejects_corrupted_frames() {
+        let line = encode(7, "MOVE:2:FWD:100");
+        let corrupted = line.replace("100", "900");
+        assert!(matches!(decode(&corrupted), Err(FrameError::Crc { .. })));
+
+        assert_eq!(decode("MOVE:2:FWD:100"), Err(FrameError::Unframed));
+        assert_eq!(decode("@garbage"), Err(FrameError::Malformed));
+        assert_eq!(decode("@1|7|ACK:7|XYZW"), Err(FrameError::Malformed));
+    }
+
+    #[test]
+    fn decode_rejects_other_versions_and_bad_sequence_numbers() {
+        let inner = "2|7|ACK:7";
+        let line = format!("@{}|{:04X}", inner, crc16(inner.as_bytes()));
+        assert_eq!(decode(&line), Err(FrameError::Version("2".to_string())));
+
+        let inner = "1|seven|ACK:7";
+        let line = format!("@{}|{:04X}", inner, crc16(inner.as_bytes()));
+        assert_eq!(decode(&line), Err(FrameError::Malformed));
+
+        let inner = "1|7";
+        let line = format!("@{}|{:04X}", inner, crc16(inner.as_bytes()));
+        assert_eq!(decode(&line), Err(FrameError::Malformed));
+    }
+}
@@ -1,77 +1,552 @@
-use crate::esp32::{EspMessage, SerialHandler};
-use tokio::time::{timeout, Duration};
+use crate::config::SerialConfig;
+use crate::esp32::capture::Capture;
+use crate::esp32::{discovery, frame};
+use crate::esp32::serial::{SerialReader, SerialWriter};
+use crate::esp32::{EspCommand, EspEvent, EspMessage, FirmwareInfo, FirmwareVersion, SerialHandler};
+use crate::monitoring::{HEALTH, METRICS};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
 use tokio::io;
+use tracing::{debug, error, info, trace, warn};
+
+/// Reply to a message sent with `send_with_retry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EspReply {
+    Ack,
+    Err,
+}
+
+/// How an accepted motion command ended, from the ESP32's `DONE` / `FAIL` event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Completion {
+    /// Finished after moving `steps`
+    Done { steps: u32 },
+    /// Stopped early; `reason` as sent by the ESP32 (e.g. LIMIT, ESTOP, STALL)
+    Failed { reason: String, steps: u32 },
+}
+
+/// Sends `ESTOP` straight to the port(s), ahead of any command waiting for its reply.
+#[derive(Clone)]
+pub struct EmergencyStop {
+    targets: Vec<(SerialWriter, Arc<AtomicU16>)>,
+}
+
+impl EmergencyStop {
+    /// One emergency stop reaching every controller of `stops`.
+    pub fn all(stops: impl IntoIterator<Item = EmergencyStop>) -> Self {
+        Self { targets: stops.into_iter().flat_map(|stop| stop.targets).collect() }
+    }
+
+    // Fire and forget: the ESP32 answers with an ESTOP event, and fails
+    // whatever command was in flight with ERR. Every controller is tried even
+    // if one cannot be reached; the first failure is returned.
+    pub async fn trigger(&self) -> io::Result<()> {
+        let msg = EspMessage::command(EspCommand::EStop);
+        let mut result = Ok(());
+        for (writer, seq) in &self.targets {
+            let seq = seq.fetch_add(1, Ordering::Relaxed);
+            warn!(message = %msg, seq, "sending emergency stop");
+            if let Err(e) = writer.send(&frame::encode(seq, &msg.to_string())).await {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+/// State of the link as seen by the ping watchdog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkHealth {
+    /// Round trip of the last answered ping
+    pub rtt_ms: Option<u64>,
+    /// Pings in a row that went unanswered
+    pub ping_failures: u32,
+}
+
+type Pending = Arc<Mutex<HashMap<u16, oneshot::Sender<EspReply>>>>;
+
+/// Firmware details from the last `VERSION` reply, written by the reader task.
+type Firmware = Arc<Mutex<Option<FirmwareInfo>>>;
 
 pub struct EspHandler {
-    serial: SerialHandler,
+    writer: SerialWriter,
     max_retries: u8,
     retry_delay: Duration,
     ack_timeout: Duration,
+    pending: Pending,        // requests waiting for ACK / ERR, by sequence number
+    event_rx: mpsc::UnboundedReceiver<EspEvent>,
+    events: Vec<EspEvent>,   // received events not yet consumed, oldest first
+    seq: Arc<AtomicU16>,     // next frame sequence number, shared with EmergencyStop
+    firmware: Firmware,      // what the connected board reported in the handshake
+    last_reply: Instant,     // last ACK / ERR, so the watchdog only pings an idle link
+    rtt: Option<Duration>,
+    ping_failures: u32,
+    reader: Option<JoinHandle<()>>, // finished once the port is gone
+    serial: Option<SerialConfig>,   // where to find the port again after a disconnect
+    capture: Option<Capture>,       // records the traffic of every port opened
 }
 
 impl EspHandler {
+    // Takes over the port and starts the reader task demultiplexing its input.
     pub fn new(serial: SerialHandler) -> Self {
+        let (lines, writer) = serial.into_parts();
+        let mut handler = Self::with_writer(writer, None);
+        handler.spawn_reader(lines);
+        handler
+    }
+
+    // Locates and opens the ESP32, and can reopen it with `reconnect` after it
+    // is unplugged. Starts disconnected if the ESP32 cannot be found yet.
+    pub async fn open(config: SerialConfig) -> Self {
+        let capture = config.capture.as_ref().and_then(|capture| match Capture::start(capture, &config.name) {
+            Ok(capture) => Some(capture),
+            Err(e) => {
+                error!(controller = config.name, error = %e, "cannot capture serial traffic");
+                None
+            }
+        });
+        let mut handler = Self::with_writer(SerialWriter::default(), Some(config));
+        handler.capture = capture;
+        if let Err(e) = handler.reconnect().await {
+            warn!(error = %e, "ESP32 not available yet");
+        }
+        handler
+    }
+
+    fn with_writer(writer: SerialWriter, serial: Option<SerialConfig>) -> Self {
         Self { 
-            serial,
+            writer,
             max_retries: 3,
             retry_delay: Duration::from_millis(30), 
             ack_timeout: Duration::from_millis(200), 
+            pending: Pending::default(),
+            event_rx: mpsc::unbounded_channel().1,
+            events: Vec::new(),
+            seq: Arc::new(AtomicU16::new(0)),
+            firmware: Firmware::default(),
+            last_reply: Instant::now(),
+            rtt: None,
+            ping_failures: 0,
+            reader: None,
+            serial,
+            capture: None,
         }     
     }
 
+    fn spawn_reader(&mut self, lines: SerialReader) {
+        // Keep whatever the previous reader delivered before swapping channels
+        while let Ok(event) = self.event_rx.try_recv() {
+            self.events.push(event);
+        }
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        self.event_rx = event_rx;
+        self.firmware.lock().unwrap().take();
+        let reader = read_frames(
+            lines,
+            self.writer.clone(),
+            Arc::clone(&self.pending),
+            Arc::clone(&self.firmware),
+            event_tx,
+        );
+        self.reader = Some(tokio::spawn(reader));
+    }
+
+    // True while the port is open and being read.
+    pub fn is_connected(&self) -> bool {
+        self.reader.as_ref().is_some_and(|reader| !reader.is_finished())
+    }
+
+    // Finds the ESP32 again (it may have a new device path after a replug) and reopens it.
+    pub async fn reconnect(&mut self) -> io::Result<()> {
+        let config = self.serial.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Unsupported, "port was opened directly and cannot be reopened")
+        })?;
+        let (path, serial) = discovery::open(config, self.capture.clone()).await?;
+        // The old reader must not detach the new port on its way out
+        if let Some(old) = self.reader.take() {
+            old.abort();
+        }
+        let min_firmware = config.min_firmware;
+        let lines = self.writer.attach(serial).await;
+        self.spawn_reader(lines);
+        self.last_reply = Instant::now();
+        self.rtt = None;
+        self.ping_failures = 0;
+        info!(port = path, "serial port opened");
+
+        // A board that cannot identify itself, or runs firmware that is too old, is not used
+        if let Err(e) = self.handshake(min_firmware).await {
+            self.close().await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Asks the firmware to identify itself and checks it is at least `min_version`.
+    pub async fn handshake(&mut self, min_version: FirmwareVersion) -> io::Result<FirmwareInfo> {
+        let msg = EspMessage::command(EspCommand::Info);
+        let reply = self.send_with_retry(&msg.to_string()).await?;
+        let info = match (reply, self.firmware()) {
+            (EspReply::Ack, Some(info)) => info,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "ESP32 did not report its firmware version",
+                ));
+            }
+        };
+        if info.version < min_version {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("board {} runs firmware {}, older than the minimum {}", info.board, info.version, min_version),
+            ));
+        }
+        info!(version = %info.version, board = info.board, commands = ?info.commands, "ESP32 firmware");
+        HEALTH.set_esp_firmware(self.name(), info.clone());
+        Ok(info)
+    }
+
+    // Firmware of the connected board, once the handshake has run.
+    pub fn firmware(&self) -> Option<FirmwareInfo> {
+        self.firmware.lock().unwrap().clone()
+    }
+
+    // Whether the board implements `cmd`; assumed true before the handshake.
+    pub fn supports(&self, cmd: EspCommand) -> bool {
+        self.firmware.lock().unwrap().as_ref().is_none_or(|info| info.supports(cmd))
+    }
+
+    // Controller name from the configuration.
+    pub fn name(&self) -> &str {
+        self.serial.as_ref().map_or("main", |config| &config.name)
+    }
+
+    pub fn link_health(&self) -> LinkHealth {
+        LinkHealth {
+            rtt_ms: self.rtt.map(|rtt| rtt.as_millis() as u64),
+            ping_failures: self.ping_failures,
+        }
+    }
+
+    // Pings the ESP32 once the link has been quiet for the configured interval.
+    // After too many missed pings the board is reset and the port closed;
+    // `reconnect` then reopens it and runs the handshake again.
+    //
+    // Firmware that does not list PING is asked for INFO instead, which every
+    // board that passed the handshake answers.
+    pub async fn watchdog(&mut self) {
+        let Some(config) = &self.serial else { return };
+        let (interval, max_failures, reset) = (config.ping_interval, config.ping_failures, config.reset_on_loss);
+        if !self.is_connected() || self.last_reply.elapsed() < interval {
+            return;
+        }
+
+        let probe = if self.supports(EspCommand::Ping) { EspCommand::Ping } else { EspCommand::Info };
+        let msg = EspMessage::command(probe);
+        let sent = Instant::now();
+        match self.exchange(&msg.to_string(), self.ack_timeout, 1).await {
+            Ok(_) => {
+                let rtt = sent.elapsed();
+                METRICS.esp_rtt_seconds.with_label_values(&[self.name()]).set(rtt.as_secs_f64());
+                trace!(?rtt, "ping answered");
+                self.rtt = Some(rtt);
+                self.ping_failures = 0;
+                return;
+            }
+            Err(e) => {
+                self.ping_failures += 1;
+                // Try again on the next tick rather than a whole interval later
+                self.last_reply = Instant::now() - interval;
+                warn!(error = %e, failures = self.ping_failures, "ESP32 missed a ping");
+            }
+        }
+        if self.ping_failures < max_failures {
+            return;
+        }
+
+        error!(failures = self.ping_failures, "ESP32 not responding, dropping the link");
+        self.close().await;
+        if !reset {
+            return;
+        }
+        METRICS.esp_resets.inc();
+        let Some(config) = &self.serial else { return };
+        if let Err(e) = discovery::reset(config).await {
+            warn!(error = %e, "could not reset the ESP32");
+        }
+    }
+
+    // Stops reading and releases the port. Waits for the reader to be dropped
+    // along with its half of the port, so the port can be opened again right away.
+    async fn close(&mut self) {
+        if let Some(reader) = self.reader.take() {
+            reader.abort();
+            // Cancelled, or finished on its own if the port was already gone
+            let _ = reader.await;
+        }
+        self.pending.lock().unwrap().clear();
+        self.writer.detach().await;
+    }
+
+    // Number the next message `seq`, to line up with a captured session being replayed.
+    pub fn set_next_seq(&self, seq: u16) {
+        self.seq.store(seq, Ordering::Relaxed);
+    }
+
+    // Dedicated path for stopping the motors from another task.
+    pub fn emergency_stop(&self) -> EmergencyStop {
+        EmergencyStop { targets: vec![(self.writer.clone(), Arc::clone(&self.seq))] }
+    }
+
     // Sends a message and retries until an ACK or ERR is received.
-    pub async fn send_with_retry(&mut self, msg: &str) -> io::Result<()> {
+    pub async fn send_with_retry(&mut self, msg: &str) -> io::Result<EspReply> {
+        self.send_with_timeout(msg, self.ack_timeout).await
+    }
+
+    // Same as send_with_retry, waiting up to `reply_timeout` for each attempt.
+    pub async fn send_with_timeout(&mut self, msg: &str, reply_timeout: Duration) -> io::Result<EspReply> {
+        self.exchange(msg, reply_timeout, self.max_retries).await
+    }
+
+    async fn exchange(&mut self, msg: &str, reply_timeout: Duration, attempts: u8) -> io::Result<EspReply> {
+        if !self.is_connected() {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "serial port closed"));
+        }
         let text = msg.trim();
-        println!("[ESP32_Handler] Sending message: '{}'", text);
+        // Every attempt reuses the sequence number, so the ESP32 can tell a retry from a new command
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let framed = frame::encode(seq, text);
+        debug!(message = text, seq, "sending message");
+
+        // Registered before sending so a fast reply cannot be missed; a late
+        // reply to an earlier attempt still answers this request
+        let (reply_tx, mut reply_rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(seq, reply_tx);
 
-        for attempt in 1..=self.max_retries {
-            self.serial.send(&text).await?;
+        for attempt in 1..=attempts {
+            if let Err(e) = self.writer.send(&framed).await {
+                self.pending.lock().unwrap().remove(&seq);
+                return Err(e);
+            }
 
             // wait up to X seconds for a reply
-            match timeout(self.ack_timeout, self.serial.read_line()).await {
-                
-                // Deal with String
-                Ok(Ok(reply)) => {
-                    println!("{}", reply);
-                    let reply_trimmed = reply.trim();
-                    if reply_trimmed == "ACK" {
-                        println!("[ESP32_Handler] Got ACK on attempt {}", attempt);
-                        return Ok(());
-                    }
-                    if reply_trimmed== "ERR" {
-                        println!("[ESP32_Handler] Got ERR on attempt {}", attempt);
-                        return Ok(());
-                    }
+            match timeout(reply_timeout, &mut reply_rx).await {
+                Ok(Ok(EspReply::Ack)) => {
+                    METRICS.esp_replies.with_label_values(&["ack"]).inc();
+                    HEALTH.mark_esp_ack();
+                    self.last_reply = Instant::now();
+                    debug!(attempt, "got ACK");
+                    return Ok(EspReply::Ack);
+                }
+                Ok(Ok(EspReply::Err)) => {
+                    METRICS.esp_replies.with_label_values(&["err"]).inc();
+                    self.last_reply = Instant::now();
+                    warn!(attempt, message = text, "got ERR");
+                    return Ok(EspReply::Err);
                 }
-                Ok(Err(e)) => {
-                    println!("[ESP32_Handler] Error reading reply: {}", e);
+                Ok(Err(_)) => {
+                    // The reader task dropped every pending request on its way out
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "serial port closed"));
                 }
 
                 Err(_) => {
-                    println!("[ESP32_Handler] Timeout waiting for reply on attempt {}", attempt);
+                    METRICS.esp_timeouts.inc();
+                    warn!(attempt, "timeout waiting for reply");
                 }
             }
 
-            if attempt < self.max_retries {
+            if attempt < attempts {
                 tokio::time::sleep(self.retry_delay).await;
-                println!("[ESP32_Handler] 🔁 Retrying ({}/{})", attempt + 1, self.max_retries);
+                METRICS.esp_retries.inc();
+                info!(attempt = attempt + 1, max_retries = attempts, "retrying");
             }
            
         }
 
-        Err(tokio::io::Error::new(tokio::io::ErrorKind::Other, "No ACK received"))
+        self.pending.lock().unwrap().remove(&seq);
+        METRICS.esp_failures.inc();
+        Err(io::Error::other("No ACK received"))
+    }
+
+    // Events received so far, oldest first.
+    pub fn take_events(&mut self) -> Vec<EspEvent> {
+        while let Ok(event) = self.event_rx.try_recv() {
+            self.events.push(event);
+        }
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl Drop for EspHandler {
+    fn drop(&mut self) {
+        if let Some(reader) = &self.reader {
+            reader.abort();
+        }
+    }
+}
+
+// Reader task: owns the read half for as long as the port is open, handing
+// ACK / ERR to the request waiting on that sequence number and everything
+// else to the event channel, so nothing is lost between requests.
+async fn read_frames(
+    mut lines: SerialReader,
+    writer: SerialWriter,
+    pending: Pending,
+    firmware: Firmware,
+    events: mpsc::UnboundedSender<EspEvent>,
+) {
+    // The ESP32 may send an event again when unsure it got through; applying a
+    // DONE or JOGGED twice would shift the tracked stage position
+    let mut last_event_seq = None;
+    loop {
+        let line = match lines.next().await {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => {
+                error!(error = %e, "serial read failed");
+                break;
+            }
+            None => {
+                warn!("serial port closed");
+                break;
+            }
+        };
+        let line = line.trim();
+        trace!(reply = line, "raw reply");
+        if line.is_empty() {
+            continue;
+        }
+        let (seq, body) = match frame::decode(line) {
+            Ok(frame) => (frame.seq, frame.body),
+            Err(e) => {
+                METRICS.esp_frame_errors.inc();
+                warn!(line, error = %e, "discarding bad frame");
+                continue;
+            }
+        };
+
+        match body.split_once(':') {
+            // Answers INFO in place of an ACK
+            Some(("VERSION", rest)) => {
+                let (seq, fields) = rest.split_once(':').unwrap_or((rest, ""));
+                let Ok(reply_to) = seq.parse::<u16>() else {
+                    METRICS.esp_frame_errors.inc();
+                    warn!(body, "reply without a valid sequence number");
+                    continue;
+                };
+                let reply = match FirmwareInfo::parse(fields) {
+                    Ok(info) => {
+                        *firmware.lock().unwrap() = Some(info);
+                        EspReply::Ack
+                    }
+                    Err(e) => {
+                        warn!(body, error = %e, "unparsable firmware version");
+                        EspReply::Err
+                    }
+                };
+                if let Some(waiter) = pending.lock().unwrap().remove(&reply_to) {
+                    let _ = waiter.send(reply);
+                }
+            }
+            Some(("ACK", seq)) | Some(("ERR", seq)) => {
+                let Ok(reply_to) = seq.parse::<u16>() else {
+                    METRICS.esp_frame_errors.inc();
+                    warn!(body, "reply without a valid sequence number");
+                    continue;
+                };
+                let reply = if body.starts_with("ACK") { EspReply::Ack } else { EspReply::Err };
+                match pending.lock().unwrap().remove(&reply_to) {
+                    Some(waiter) => {
+                        let _ = waiter.send(reply);
+                    }
+                    // A retry answered twice, or a request that already gave up
+                    None => debug!(?reply, reply_to, "ignoring reply to no pending message"),
+                }
+            }
+            _ if last_event_seq.replace(seq) == Some(seq) => {
+                debug!(seq, body, "dropping repeated event");
+            }
+            _ => match body.parse::<EspMessage>() {
+                Ok(msg) => match EspEvent::from_message(&msg) {
+                    Some(event) => {
+                        debug!(?event, "device event");
+                        if events.send(event).is_err() {
+                            break; // handler dropped
+                        }
+                    }
+                    None => debug!(%msg, "ignoring message that is not an event"),
+                },
+                Err(e) => {
+                    METRICS.esp_frame_errors.inc();
+                    warn!(body, error = %e, "unparsable message");
+                }
+            },
+        }
+    }
+    // Dropping the waiters fails every request still pending
+    pending.lock().unwrap().clear();
+    writer.detach().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    async fn send(device: &mut DuplexStream, seq: u16, body: &str) {
+        device.write_all(format!("{}\n", frame::encode(seq, body)).as_bytes()).await.unwrap();
     }
 
-    // Reads and parses an incoming message from the ESP32.
-    pub async fn receive_message(&mut self) -> io::Result<EspMessage> {
-        let raw = self.serial.read_line().await?;
-        Ok(EspMessage::from_string(&raw).unwrap())
+    /// Events until (and including) the first one for `motor`.
+    async fn events_until(esp: &mut EspHandler, motor: u8) -> Vec<EspEvent> {
+        let mut events = Vec::new();
+        timeout(Duration::from_secs(1), async {
+            while !events.iter().any(|e: &EspEvent| e.motor() == Some(motor)) {
+                events.extend(esp.take_events());
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("events arrive");
+        events
     }
 
-    // Simple send without waiting for ACK
-    pub async fn send_message(&mut self, msg: &str) -> io::Result<()> {
-        println!("[ESP32_Handler] 📤 Sending (no wait): '{}'", msg);
-        self.serial.send(msg).await
+    #[tokio::test]
+    async fn repeated_events_are_delivered_once() {
+        let (host, mut device) = tokio::io::duplex(1024);
+        let mut esp = EspHandler::new(SerialHandler::from_io(host));
+
+        send(&mut device, 5, "DONE:1:FWD:20").await;
+        send(&mut device, 5, "DONE:1:FWD:20").await;
+        send(&mut device, 6, "DONE:1:FWD:20").await;
+        send(&mut device, 7, "DONE:2:BWD:3").await;
+
+        let events = events_until(&mut esp, 2).await;
+        assert_eq!(
+            events,
+            vec![
+                EspEvent::Done { motor: 1, steps: 20 },
+                EspEvent::Done { motor: 1, steps: 20 },
+                EspEvent::Done { motor: 2, steps: 3 },
+            ]
+        );
     }
 
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn closing_releases_the_port_before_returning() {
+        let (host, mut device) = tokio::io::duplex(1024);
+        let mut esp = EspHandler::new(SerialHandler::from_io(host));
+        esp.close().await;
+        assert!(!esp.is_connected());
+
+        // Both halves are already dropped, so the other end sees it closed without waiting
+        let mut buf = [0u8; 16];
+        assert_eq!(device.read(&mut buf).now_or_never().map(Result::unwrap), Some(0));
+    }
+}
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::esp32::EspCommand;
+
+/// Firmware release as `major.minor.patch`, ordered for the minimum version check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for FirmwareVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parts: Vec<&str> = s.strip_prefix('v').unwrap_or(s).split('.').collect();
+        let [major, minor, patch] = parts[..] else {
+            return Err(format!("'{}' is not a major.minor.patch version", s));
+        };
+        let number = |part: &str| part.parse().map_err(|_| format!("'{}' is not a major.minor.patch version", s));
+        Ok(Self { major: number(major)?, minor: number(minor)?, patch: number(patch)? })
+    }
+}
+
+impl Serialize for FirmwareVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FirmwareVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What the ESP32 reported about itself in reply to `INFO`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirmwareInfo {
+    pub version: FirmwareVersion,
+    pub board: String,
+    /// Host commands the firmware implements, as sent on the wire (e.g. MOVE)
+    pub commands: Vec<String>,
+}
+
+impl FirmwareInfo {
+    pub fn supports(&self, cmd: EspCommand) -> bool {
+        self.commands.iter().any(|c| c == cmd.as_str())
+    }
+
+    /// Read the fields of a `VERSION:<seq>:<version>:<board>:<commands>` reply,
+    /// given everything after the sequence number.
+    pub fn parse(fields: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = fields.split(':').collect();
+        let [version, board, commands] = parts[..] else {
+            return Err(format!("expected <version>:<board>:<commands>, got '{}'", fields));
+        };
+        Ok(Self {
+            version: version.parse()?,
+            board: board.to_string(),
+            commands: commands.split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> FirmwareVersion {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_versions_with_or_without_a_leading_v() {
+        assert_eq!(version("1.2.3"), FirmwareVersion { major: 1, minor: 2, patch: 3 });
+        assert_eq!(version("v10.0.7"), FirmwareVersion { major: 10, minor: 0, patch: 7 });
+        assert_eq!(version(" 2.0.0 "), FirmwareVersion { major: 2, minor: 0, patch: 0 });
+        assert_eq!(version("1.2.3").to_string(), "1.2.3");
+    }
+
+    #[test]
+    fn rejects_malformed_versions() {
+        for s in ["", "1.2", "1.2.3.4", "1.x.3", "1..3", "-1.0.0", "70000.0.0", "V1.2.3"] {
+            assert!(s.parse::<FirmwareVersion>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn orders_numerically_by_major_minor_patch() {
+        assert!(version("1.10.0") > version("1.9.9"));
+        assert!(version("2.0.0") > version("1.99.99"));
+        assert!(version("1.0.1") > version("1.0.0"));
+        assert_eq!(version("v1.0.0"), version("1.0.0"));
+    }
+
+    #[test]
+    fn serializes_as_a_string() {
+        let v = version("1.4.2");
+        assert_eq!(serde_json::to_string(&v).unwrap(), "\"1.4.2\"");
+        assert_eq!(serde_json::from_str::<FirmwareVersion>("\"1.4.2\"").unwrap(), v);
+        assert!(serde_json::from_str::<FirmwareVersion>("\"1.4\"").is_err());
+    }
+
+    #[test]
+    fn parses_the_version_reply() {
+        let info = FirmwareInfo::parse("1.3.0:bioscope-s3:MOVE,HOME,PING,").unwrap();
+        assert_eq!(info.version, version("1.3.0"));
+        assert_eq!(info.board, "bioscope-s3");
+        assert_eq!(info.commands, vec!["MOVE", "HOME", "PING"]);
+        assert!(info.supports(EspCommand::Move));
+        assert!(info.supports(EspCommand::Ping));
+        assert!(!info.supports(EspCommand::Jog));
+    }
+
+    #[test]
+    fn accepts_firmware_without_commands() {
+        let info = FirmwareInfo::parse("1.0.0:proto:").unwrap();
+        assert!(info.commands.is_empty());
+        assert!(!info.supports(EspCommand::Move));
+    }
+
+    #[test]
+    fn rejects_malformed_version_replies() {
+        assert!(FirmwareInfo::parse("1.0.0:proto").is_err());
+        assert!(FirmwareInfo::parse("1.0.0:proto:MOVE:extra").is_err());
+        assert!(FirmwareInfo::parse("one:proto:MOVE").is_err());
+        assert!(FirmwareInfo::parse("").is_err());
+    }
+}
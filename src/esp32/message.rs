@@ -1,35 +1,346 @@
-#[derive(Debug)]
+use std::fmt;
+use std::str::FromStr;
+
+// Wire format: CMD:MOTOR:DIRECTION:STEPS[:SPEED[:ACCEL]], sent inside a frame (see frame.rs).
+// ACK / ERR below stand for the framed ACK:<seq> / ERR:<seq> reply.
+//   MOVE:<motor>:<FWD|BWD>:<steps>[:<speed>[:<accel>]]
+//                                    relative move, ACK once accepted, then DONE / FAIL;
+//                                    <speed> in steps/s and <accel> in steps/s² override
+//                                    the motor's defaults for this move (empty: default)
+//   SET:<motor>:<SPEED|ACCEL>:<value>  motor's default speed (steps/s) or acceleration
+//                                    (steps/s²), ACK once stored; firmware that lists SET
+//                                    also reads the MOVE speed and accel fields
+//   HOME:<motor>:BWD:0               drive to the endstop, ACK once accepted, DONE once homed
+//   JOG:<motor>:<FWD|BWD>:<speed>    move continuously at <speed> steps/s; sending it
+//                                    again is the keepalive that resets the ESP32's
+//                                    dead-man timer, which stops the motor on expiry
+//   JOGSTOP:<motor>:<FWD|BWD>:0      stop a jog, ACK once stopped (also when idle)
+//   ESTOP:0::0                       stop every motor now and refuse motion until RESET;
+//                                    not acknowledged, the command in flight gets ERR
+//   RESET:0::0                       clear the ESP32's emergency stop, ACK when clear
+//   PING:0::0                        liveness check, ACK at once (the host sends INFO
+//                                    instead to firmware that does not list PING)
+//   INFO:0::0                        answered with VERSION instead of ACK:
+//     VERSION:<seq>:<version>:<board>:<commands>  firmware major.minor.patch, board id
+//                                    and the host commands it implements, comma-separated
+//   LIMIT:<motor>:<FWD|BWD>:0        (from ESP32) limit switch triggered
+//   JOGGED:<motor>:<FWD|BWD>:<steps> (from ESP32) a jog ended after <steps>, whether
+//                                    stopped by JOGSTOP, a limit or the dead-man timer
+//   ESTOP:0::0                       (from ESP32) emergency stop carried out
+//   STALL:<motor>:<FWD|BWD>:0        (from ESP32) driver detected a stall
+//   FAULT:<motor>:<code>:0           (from ESP32) driver fault, motor disabled
+//   BUTTON:<id>:<PRESS|RELEASE>:0    (from ESP32) front panel button
+//   SENSOR:0:<name>:<value>          (from ESP32) sensor reading, raw value
+//   DONE:<motor>:<FWD|BWD>:<steps>   (from ESP32) MOVE / HOME finished after <steps>
+//   FAIL:<motor>:<reason>:<steps>    (from ESP32) MOVE / HOME stopped early after <steps>,
+//                                    <reason> e.g. LIMIT, ESTOP, STALL
+/// Command field of a message, in either direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EspCommand {
+    Move,
+    Home,
+    Jog,
+    JogStop,
+    EStop,
+    Reset,
+    Set,
+    Info,
+    Ping,
+    Limit,
+    Jogged,
+    Done,
+    Fail,
+    Stall,
+    Fault,
+    Button,
+    Sensor,
+}
+
+impl EspCommand {
+    pub const ALL: [EspCommand; 17] = [
+        EspCommand::Move,
+        EspCommand::Home,
+        EspCommand::Jog,
+        EspCommand::JogStop,
+        EspCommand::EStop,
+        EspCommand::Reset,
+        EspCommand::Set,
+        EspCommand::Info,
+        EspCommand::Ping,
+        EspCommand::Limit,
+        EspCommand::Jogged,
+        EspCommand::Done,
+        EspCommand::Fail,
+        EspCommand::Stall,
+        EspCommand::Fault,
+        EspCommand::Button,
+        EspCommand::Sensor,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EspCommand::Move => "MOVE",
+            EspCommand::Home => "HOME",
+            EspCommand::Jog => "JOG",
+            EspCommand::JogStop => "JOGSTOP",
+            EspCommand::EStop => "ESTOP",
+            EspCommand::Reset => "RESET",
+            EspCommand::Set => "SET",
+            EspCommand::Info => "INFO",
+            EspCommand::Ping => "PING",
+            EspCommand::Limit => "LIMIT",
+            EspCommand::Jogged => "JOGGED",
+            EspCommand::Done => "DONE",
+            EspCommand::Fail => "FAIL",
+            EspCommand::Stall => "STALL",
+            EspCommand::Fault => "FAULT",
+            EspCommand::Button => "BUTTON",
+            EspCommand::Sensor => "SENSOR",
+        }
+    }
+}
+
+impl fmt::Display for EspCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EspCommand {
+    type Err = EspParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EspCommand::ALL
+            .into_iter()
+            .find(|cmd| cmd.as_str() == s)
+            .ok_or_else(|| EspParseError::UnknownCommand(s.to_string()))
+    }
+}
+
+/// Why a line could not be read as an `EspMessage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EspParseError {
+    /// Expected four to six ':'-separated fields
+    FieldCount(usize),
+    UnknownCommand(String),
+    BadMotor(String),
+    /// Direction contains ':' or a control character, so it would not survive the wire
+    BadDirection(String),
+    BadSteps(String),
+    BadSpeed(String),
+    BadAccel(String),
+}
+
+impl fmt::Display for EspParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EspParseError::FieldCount(n) => {
+                write!(f, "expected 4 to 6 fields (CMD:MOTOR:DIRECTION:STEPS[:SPEED[:ACCEL]]), got {}", n)
+            }
+            EspParseError::UnknownCommand(cmd) => write!(f, "unknown command '{}'", cmd),
+            EspParseError::BadMotor(motor) => write!(f, "motor '{}' is not a number from 0 to 255", motor),
+            EspParseError::BadDirection(direction) => {
+                write!(f, "direction {:?} must not contain ':' or control characters", direction)
+            }
+            EspParseError::BadSteps(steps) => write!(f, "steps '{}' is not a non-negative number", steps),
+            EspParseError::BadSpeed(speed) => write!(f, "speed '{}' is not a non-negative number", speed),
+            EspParseError::BadAccel(accel) => write!(f, "acceleration '{}' is not a non-negative number", accel),
+        }
+    }
+}
+
+impl std::error::Error for EspParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EspMessage {
-    pub cmd: String,
-    pub motor: Option<u8>,
-    pub direction: Option<String>,
-    pub steps: Option<u32>,
+    pub cmd: EspCommand,
+    pub motor: u8,                 // 0 for commands without a motor
+    direction: Option<String>,     // empty on the wire when `None`, never `Some("")`
+    pub steps: u32,
+    pub speed: Option<u32>,        // steps/s, MOVE only; omitted on the wire when `None`
+    pub accel: Option<u32>,        // steps/s², MOVE only; omitted on the wire when `None`
 }
 
 impl EspMessage {
+    /// A command that takes no arguments (e.g. ESTOP, RESET).
+    pub fn command(cmd: EspCommand) -> Self {
+        Self { cmd, motor: 0, direction: None, steps: 0, speed: None, accel: None }
+    }
+
+    /// A message with a direction field, which is left empty on the wire when `direction` is.
+    pub fn new(cmd: EspCommand, motor: u8, direction: &str, steps: u32) -> Result<Self, EspParseError> {
+        Ok(Self { cmd, motor, direction: direction_field(direction)?, steps, speed: None, accel: None })
+    }
 
-    // Convert struct → string
-    pub fn to_string(&self) -> String {
-        format!(
+    /// Direction field (FWD / BWD, or a reason, code or name depending on the command).
+    pub fn direction(&self) -> Option<&str> {
+        self.direction.as_deref()
+    }
+}
+
+/// Only directions that read back the same are allowed: an empty one is `None`.
+fn direction_field(direction: &str) -> Result<Option<String>, EspParseError> {
+    if direction.chars().any(|c| c == ':' || c.is_control()) {
+        return Err(EspParseError::BadDirection(direction.to_string()));
+    }
+    Ok((!direction.is_empty()).then(|| direction.to_string()))
+}
+
+impl fmt::Display for EspMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "{}:{}:{}:{}",
             self.cmd,
-            self.motor.unwrap_or(0),
-             self.direction.clone().unwrap_or_default(),
-            self.steps.unwrap_or(0)
-        )
+            self.motor,
+            self.direction.as_deref().unwrap_or_default(),
+            self.steps
+        )?;
+        match (self.speed, self.accel) {
+            (None, None) => Ok(()),
+            (Some(speed), None) => write!(f, ":{}", speed),
+            (speed, Some(accel)) => write!(f, ":{}:{}", speed.map(|s| s.to_string()).unwrap_or_default(), accel),
+        }
     }
+}
+
+impl FromStr for EspMessage {
+    type Err = EspParseError;
 
-    // take data string and convert into 
-    pub fn from_string(data: &str) -> Option<EspMessage> {
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = data.trim().split(':').collect();
-        if parts.len() != 4 {
-            return None;
-        }
-        Some(EspMessage {
-            cmd: parts[0].to_string(),
-            motor: parts[1].parse().ok(),
-            direction: Some(parts[2].to_string()),
-            steps: parts[3].parse().ok(),
+        let (&[cmd, motor, direction, steps], profile) = parts.split_at(parts.len().min(4)) else {
+            return Err(EspParseError::FieldCount(parts.len()));
+        };
+        if profile.len() > 2 {
+            return Err(EspParseError::FieldCount(parts.len()));
+        }
+        // Trailing fields are optional and may be empty
+        let field = |n: usize, error: fn(String) -> EspParseError| -> Result<Option<u32>, EspParseError> {
+            match profile.get(n) {
+                None | Some(&"") => Ok(None),
+                Some(value) => value.parse().map(Some).map_err(|_| error(value.to_string())),
+            }
+        };
+        Ok(EspMessage {
+            cmd: cmd.parse()?,
+            motor: motor.parse().map_err(|_| EspParseError::BadMotor(motor.to_string()))?,
+            direction: direction_field(direction)?,
+            steps: steps.parse().map_err(|_| EspParseError::BadSteps(steps.to_string()))?,
+            speed: field(0, EspParseError::BadSpeed)?,
+            accel: field(1, EspParseError::BadAccel)?,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn any_command() -> impl Strategy<Value = EspCommand> {
+        proptest::sample::select(EspCommand::ALL.to_vec())
+    }
+
+    fn any_message() -> impl Strategy<Value = EspMessage> {
+        (
+            any_command(),
+            any::<u8>(),
+            // Anything but ':' and control characters, including the empty string
+            "[^:\\p{Cc}]{0,12}",
+            any::<u32>(),
+            any::<Option<u32>>(),
+            any::<Option<u32>>(),
+        )
+            .prop_map(|(cmd, motor, direction, steps, speed, accel)| {
+                let mut msg = EspMessage::new(cmd, motor, &direction, steps).unwrap();
+                (msg.speed, msg.accel) = (speed, accel);
+                msg
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn messages_round_trip(msg in any_message()) {
+            prop_assert_eq!(msg.to_string().parse::<EspMessage>(), Ok(msg));
+        }
+
+        #[test]
+        fn accepted_directions_round_trip(direction in "\\PC*", motor: u8, steps: u32) {
+            match EspMessage::new(EspCommand::Move, motor, &direction, steps) {
+                Ok(msg) => prop_assert_eq!(msg.to_string().parse::<EspMessage>(), Ok(msg)),
+                Err(e) => {
+                    prop_assert!(direction.contains(':'));
+                    prop_assert_eq!(e, EspParseError::BadDirection(direction.clone()));
+                }
+            }
+        }
+
+        #[test]
+        fn parsing_arbitrary_input_never_panics(line in "\\PC{0,40}") {
+            let _ = line.parse::<EspMessage>();
+        }
+    }
+
+    #[test]
+    fn every_command_round_trips() {
+        for cmd in EspCommand::ALL {
+            assert_eq!(cmd.as_str().parse(), Ok(cmd));
+            let msg = EspMessage::command(cmd);
+            assert_eq!(msg.to_string().parse(), Ok(msg));
+        }
+    }
+
+    #[test]
+    fn empty_direction_is_none() {
+        let msg = EspMessage::new(EspCommand::Move, 1, "", 5).unwrap();
+        assert_eq!(msg.direction(), None);
+        assert_eq!(msg.to_string(), "MOVE:1::5");
+    }
+
+    #[test]
+    fn profile_fields_are_optional_and_may_be_empty() {
+        let msg: EspMessage = "MOVE:2:FWD:100".parse().unwrap();
+        assert_eq!((msg.speed, msg.accel), (None, None));
+        let msg: EspMessage = "MOVE:2:FWD:100:500".parse().unwrap();
+        assert_eq!((msg.speed, msg.accel), (Some(500), None));
+        let msg: EspMessage = "MOVE:2:FWD:100::2000".parse().unwrap();
+        assert_eq!((msg.speed, msg.accel), (None, Some(2000)));
+        assert_eq!(msg.to_string(), "MOVE:2:FWD:100::2000");
+        let msg: EspMessage = "MOVE:2:FWD:100:500:2000".parse().unwrap();
+        assert_eq!((msg.speed, msg.accel), (Some(500), Some(2000)));
+    }
+
+    #[test]
+    fn rejects_wrong_field_counts() {
+        assert_eq!("MOVE:2:FWD".parse::<EspMessage>(), Err(EspParseError::FieldCount(3)));
+        assert_eq!("".parse::<EspMessage>(), Err(EspParseError::FieldCount(1)));
+        assert_eq!("MOVE:2:FWD:1:2:3:4".parse::<EspMessage>(), Err(EspParseError::FieldCount(7)));
+    }
+
+    #[test]
+    fn reports_each_bad_field() {
+        assert_eq!("WALK:2:FWD:1".parse::<EspMessage>(), Err(EspParseError::UnknownCommand("WALK".to_string())));
+        assert_eq!("move:2:FWD:1".parse::<EspMessage>(), Err(EspParseError::UnknownCommand("move".to_string())));
+        assert_eq!("MOVE:256:FWD:1".parse::<EspMessage>(), Err(EspParseError::BadMotor("256".to_string())));
+        assert_eq!("MOVE:-1:FWD:1".parse::<EspMessage>(), Err(EspParseError::BadMotor("-1".to_string())));
+        assert_eq!("MOVE:2:FWD:".parse::<EspMessage>(), Err(EspParseError::BadSteps(String::new())));
+        assert_eq!("MOVE:2:FWD:1.5".parse::<EspMessage>(), Err(EspParseError::BadSteps("1.5".to_string())));
+        assert_eq!("MOVE:2:FWD:1:fast".parse::<EspMessage>(), Err(EspParseError::BadSpeed("fast".to_string())));
+        assert_eq!("MOVE:2:FWD:1:5:-2".parse::<EspMessage>(), Err(EspParseError::BadAccel("-2".to_string())));
+        assert_eq!(
+            "MOVE:2:F\u{7}D:1".parse::<EspMessage>(),
+            Err(EspParseError::BadDirection("F\u{7}D".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_directions_that_would_not_survive_the_wire() {
+        for direction in ["A:B", ":", "FWD\n", "F\rWD"] {
+            assert_eq!(
+                EspMessage::new(EspCommand::Move, 1, direction, 5),
+                Err(EspParseError::BadDirection(direction.to_string()))
+            );
+        }
+    }
+}
@@ -1,8 +1,17 @@
+pub mod capture;
+pub mod controllers;
+pub mod discovery;
+pub mod event;
+pub mod frame;
 pub mod handler;
+pub mod info;
 pub mod message;
 pub mod serial;
 
 
-pub use handler::EspHandler;
-pub use message::EspMessage;
+pub use controllers::Controllers;
+pub use event::EspEvent;
+pub use handler::{Completion, EmergencyStop, EspHandler, EspReply, LinkHealth};
+pub use info::{FirmwareInfo, FirmwareVersion};
+pub use message::{EspCommand, EspMessage};
 pub use serial::SerialHandler;
\ No newline at end of file
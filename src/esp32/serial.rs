@@ -1,27 +1,180 @@
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio_serial::{SerialPortBuilderExt, SerialStream};
+use futures::SinkExt;
+use std::sync::Arc;
+use tokio::io::{self, AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+use tokio::sync::Mutex;
+use tokio_serial::SerialPortBuilderExt;
+use tokio_util::bytes::{BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
+use tracing::warn;
+
+/// Longest line kept while waiting for a newline; anything longer is noise.
+const MAX_LINE: usize = 1024;
+
+use crate::esp32::capture::{Capture, Recorded};
+
+/// Byte stream the handler talks through: the serial port, possibly wrapped
+/// for capture, or a stand-in for replaying a capture.
+pub trait SerialIo: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> SerialIo for T {}
+
+type Port = Box<dyn SerialIo>;
+
+/// Incoming lines, buffered across reads so nothing past a newline is lost.
+pub type SerialReader = FramedRead<ReadHalf<Port>, LineCodec>;
+
+type PortWriter = FramedWrite<WriteHalf<Port>, LineCodec>;
 
 pub struct SerialHandler {
-    port: SerialStream,
+    reader: SerialReader,
+    writer: PortWriter,
 }
 
+/// Shared write half of the port. Each line is written under the lock, so
+/// lines from different tasks never interleave.
+///
+/// Empty while the port is closed; every clone sees the port once it is reopened.
+#[derive(Clone, Default)]
+pub struct SerialWriter(Arc<Mutex<Option<PortWriter>>>);
+
 impl SerialHandler {
-    pub fn new(port_name: &str, baud_rate: u32) -> tokio_serial::Result<Self> {
+    /// Open a port, recording its traffic to `capture` if given.
+    pub fn new(port_name: &str, baud_rate: u32, capture: Option<Capture>) -> tokio_serial::Result<Self> {
         let port = tokio_serial::new(port_name, baud_rate)
             .open_native_async()?;
-        Ok(Self { port })
+        Ok(match capture {
+            Some(capture) => Self::from_io(Recorded::new(port, capture)),
+            None => Self::from_io(port),
+        })
+    }
+
+    /// Talk through any byte stream instead of a real port.
+    pub fn from_io(io: impl SerialIo + 'static) -> Self {
+        let (reader, writer) = tokio::io::split(Box::new(io) as Port);
+        Self {
+            reader: FramedRead::new(reader, LineCodec),
+            writer: FramedWrite::new(writer, LineCodec),
+        }
+    }
+
+    /// Split into the line stream, for a dedicated reader task, and the writer.
+    pub fn into_parts(self) -> (SerialReader, SerialWriter) {
+        (self.reader, SerialWriter(Arc::new(Mutex::new(Some(self.writer)))))
+    }
+}
+
+impl SerialWriter {
+    pub async fn send(&self, msg: &str) -> io::Result<()> {
+        match self.0.lock().await.as_mut() {
+            Some(writer) => writer.send(msg).await,
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "serial port not open")),
+        }
+    }
+
+    /// Take over a newly opened port, returning its line stream for the reader task.
+    pub async fn attach(&self, serial: SerialHandler) -> SerialReader {
+        *self.0.lock().await = Some(serial.writer);
+        serial.reader
+    }
+
+    /// Drop the write half after the port went away.
+    pub async fn detach(&self) {
+        self.0.lock().await.take();
+    }
+}
+
+/// Newline-delimited text. Unlike `LinesCodec`, bad input never ends the
+/// stream: invalid UTF-8 is replaced and overlong garbage is dropped.
+pub struct LineCodec;
+
+impl Decoder for LineCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<String>> {
+        if let Some(end) = src.iter().position(|&b| b == b'\n') {
+            let line = src.split_to(end + 1);
+            let text = String::from_utf8_lossy(&line[..end]);
+            return Ok(Some(text.trim_end_matches('\r').to_string()));
+        }
+        if src.len() > MAX_LINE {
+            warn!(bytes = src.len(), "dropping serial input without a newline");
+            src.clear();
+        }
+        Ok(None)
     }
+}
+
+impl Encoder<&str> for LineCodec {
+    type Error = io::Error;
 
-    pub async fn send(&mut self, msg: &str) -> tokio::io::Result<()> {
-        self.port.write_all(msg.as_bytes()).await?;
-        self.port.write_all(b"\n").await?;
+    fn encode(&mut self, line: &str, dst: &mut BytesMut) -> io::Result<()> {
+        dst.reserve(line.len() + 1);
+        dst.put_slice(line.as_bytes());
+        dst.put_u8(b'\n');
         Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(codec: &mut LineCodec, buf: &mut BytesMut) -> Vec<String> {
+        std::iter::from_fn(|| codec.decode(buf).unwrap()).collect()
+    }
+
+    #[test]
+    fn splits_lines_and_keeps_partial_input() {
+        let mut codec = LineCodec;
+        let mut buf = BytesMut::from("ACK:1\nDONE:1:FW");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec!["ACK:1"]);
+        assert_eq!(&buf[..], b"DONE:1:FW");
+
+        buf.extend_from_slice(b"D:20\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec!["DONE:1:FWD:20"]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn strips_crlf_line_endings() {
+        let mut codec = LineCodec;
+        let mut buf = BytesMut::from("ACK:1\r\n\r\nERR:2\r\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec!["ACK:1", "", "ERR:2"]);
+    }
+
+    #[test]
+    fn replaces_invalid_utf8() {
+        let mut codec = LineCodec;
+        let mut buf = BytesMut::from(&b"AC\xffK\n"[..]);
+        assert_eq!(decode_all(&mut codec, &mut buf), vec!["AC\u{fffd}K"]);
+    }
+
+    #[test]
+    fn drops_overlong_input_without_a_newline_and_recovers() {
+        let mut codec = LineCodec;
+        let mut buf = BytesMut::from(&vec![b'x'; MAX_LINE + 1][..]);
+        assert_eq!(codec.decode(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+
+        // The tail of the garbage line comes out as one bad line, then framing resumes
+        buf.extend_from_slice(b"xxx\nACK:1\n");
+        assert_eq!(decode_all(&mut codec, &mut buf), vec!["xxx", "ACK:1"]);
+    }
+
+    #[test]
+    fn keeps_a_long_line_that_ends_within_the_buffer() {
+        let mut codec = LineCodec;
+        let line = "y".repeat(MAX_LINE + 10);
+        let mut buf = BytesMut::from(format!("{}\n", line).as_str());
+        assert_eq!(decode_all(&mut codec, &mut buf), vec![line]);
+    }
 
-    pub async fn read_line(&mut self) -> tokio::io::Result<String> {
-        let mut buf_reader = BufReader::new(&mut self.port);
-        let mut line = String::new();
-        buf_reader.read_line(&mut line).await?;
-        Ok(line)
+    #[test]
+    fn encode_appends_a_newline() {
+        let mut codec = LineCodec;
+        let mut buf = BytesMut::new();
+        codec.encode("PING:0::0", &mut buf).unwrap();
+        assert_eq!(&buf[..], b"PING:0::0\n");
     }
 }
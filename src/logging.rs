@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::config::{LogConfig, LogFormat};
+
+/// Handle for swapping the active log filter while the device is running.
+#[derive(Clone)]
+pub struct LogHandle {
+    filter: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogHandle {
+    /// Replace the filter, e.g. `info,orangepi_IA::esp32=debug`.
+    pub fn set_filter(&self, directives: &str) -> anyhow::Result<()> {
+        let filter = EnvFilter::try_new(directives)?;
+        self.filter.reload(filter)?;
+        tracing::info!(filter = directives, "log filter updated");
+        Ok(())
+    }
+}
+
+/// Install the global subscriber. `RUST_LOG` wins over `LOG_LEVEL` so a
+/// one-off debug run does not need the env file edited.
+pub fn init(config: &LogConfig) -> anyhow::Result<LogHandle> {
+    let filter = EnvFilter::try_new(&config.filter)?;
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let registry = tracing_subscriber::registry().with(filter);
+    match config.format {
+        LogFormat::Text => registry.with(fmt::layer().with_target(true)).try_init()?,
+        LogFormat::Json => registry
+            .with(fmt::layer().json().with_current_span(true).with_span_list(false))
+            .try_init()?,
+    }
+
+    Ok(LogHandle { filter: handle })
+}
+
+/// Re-read the log filter from the environment on SIGHUP, so per-module levels
+/// can be changed on a running device without restarting it.
+#[cfg(unix)]
+pub fn spawn_reload_on_sighup(handle: LogHandle, env_file: Option<String>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                tracing::warn!(error = %e, "cannot listen for SIGHUP, log filter is fixed");
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            match LogConfig::reread_filter(env_file.as_deref()) {
+                Ok(directives) => {
+                    if let Err(e) = handle.set_filter(&directives) {
+                        tracing::warn!(error = %e, "invalid log filter '{}'", directives);
+                    }
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to re-read log filter"),
+            }
+        }
+    });
+}
+
+/// Lets a hot path log at most once per `interval`, counting what it skipped.
+pub struct RateLimit {
+    interval: Duration,
+    last: Option<Instant>,
+    suppressed: u64,
+}
+
+impl RateLimit {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last: None, suppressed: 0 }
+    }
+
+    /// Returns `Some(skipped)` when a log line is allowed, `None` otherwise.
+    pub fn check(&mut self) -> Option<u64> {
+        let now = Instant::now();
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
+}
@@ -1,80 +1,21 @@
 mod backend;
+mod cli;
+mod config;
 mod controllers;
 mod esp32;
+mod logging;
+mod monitoring;
+mod motion;
 
-use backend::connection::connect_wss;
-use controllers::camera::Camera;
-use backend::processor::Processor;
-use backend::listener::run_listener;
-use backend::session_state::{SessionState};
-
-use futures_util::StreamExt;
-use tokio::sync::{mpsc, RwLock};
-use std::env;
-use dotenv::dotenv;
-use std::sync::Arc;
-
+use clap::Parser;
+use cli::Cli;
 
 #[tokio::main]
 async fn main() {
-    println!("Orange Pi Device Started...");
-
-    // 1️⃣ Connect to backend
-    dotenv().ok();
-
-    let server_host = env::var("SERVER_HOST").expect("SERVER_HOST not set");
-    let server_port = env::var("SERVER_PORT").expect("SERVER_PORT not set");
-    let device_name = env::var("DEVICE_NAME").expect("DEVICE_NAME not set");
-    let auth_token = env::var("AUTH_TOKEN").expect("AUTH_TOKEN not set");
-    let url = format!(
-        "wss://{host}:{port}/orangepi/connect?device_name={name}&auth_token={token}",
-        host = server_host,
-        port = server_port,
-        name = device_name,
-        token = auth_token
-    );
-
-    println!("Connecting to backend at: {}", url);
-
-    // let url = "ws://127.0.0.1:9001"; // Local testing
-    let ws_stream = match connect_wss(&url).await {
-        Ok(stream) => stream,
-        Err(e) => {
-            eprintln!("❌ Connection failed: {}", e);
-            return;
-        }
-    };
-
-     // --- Split WebSocket ---
-    let (write, read) = ws_stream.split();
+    let cli = Cli::parse();
 
-    // --- Shared state & queue ---
-    let session_state = Arc::new(RwLock::new(SessionState::new()));
-    // session_state.write().await.connected = true; // set to StartStreaming
-
-    let (tx, rx) = mpsc::channel(100);
-
-    let mut camera = Camera::new(640, 480); // width x height
-    camera.spawn_task(Arc::clone(&session_state));
-
-    // --- Spawn listener & processor ---
-    let listener_state = Arc::clone(&session_state);
-    tokio::spawn(async move {
-        run_listener(read, tx, listener_state).await;
-    });
-
-
-    let latest_frame = camera.latest_frame();
-    let processor_state = Arc::clone(&session_state);
-    tokio::spawn(async move {
-        let mut processor = Processor::new(rx, write, processor_state, latest_frame);
-        processor.run().await;
-    });
-    
- 
-    // --- Main loop ---
-    loop {
-        tokio::time::sleep(std::time::Duration::from_secs(1000)).await;
+    if let Err(e) = cli::dispatch(cli).await {
+        eprintln!("❌ {:#}", e);
+        std::process::exit(1);
     }
 }
-
@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::backend::session_state::SessionState;
+use crate::esp32::FirmwareInfo;
+
+/// Process-wide subsystem health, reported by `/readyz`.
+pub static HEALTH: LazyLock<Health> = LazyLock::new(Health::default);
+
+/// A camera that has not produced a frame for this long during a session is down.
+const CAMERA_STALE_AFTER: Duration = Duration::from_secs(5);
+
+/// Long-running loops whose progress gates the systemd watchdog.
+#[derive(Debug, Clone, Copy)]
+pub enum Task {
+    Listener,
+    Processor,
+    Camera,
+}
+
+impl Task {
+    pub const ALL: [Task; 3] = [Task::Listener, Task::Processor, Task::Camera];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Task::Listener => "listener",
+            Task::Processor => "processor",
+            Task::Camera => "camera",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Health {
+    backend_connected: AtomicBool,
+    camera_opened: AtomicBool,
+    esp_connected: AtomicBool,
+    last_frame: Mutex<Option<Instant>>,
+    last_esp_ack: Mutex<Option<Instant>>,
+    esp_firmware: Mutex<BTreeMap<String, FirmwareInfo>>,
+    task_progress: [Mutex<Option<Instant>>; 3],
+}
+
+impl Health {
+    /// Record that `task` just went round its loop.
+    pub fn tick(&self, task: Task) {
+        *self.task_progress[task as usize].lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Tasks that have not ticked within `max_age` (or never started).
+    pub fn stalled_tasks(&self, max_age: Duration) -> Vec<&'static str> {
+        Task::ALL
+            .into_iter()
+            .filter(|&task| age(&self.task_progress[task as usize]).is_none_or(|a| a >= max_age))
+            .map(Task::name)
+            .collect()
+    }
+
+    pub fn backend_connected(&self) -> bool {
+        self.backend_connected.load(Ordering::Relaxed)
+    }
+
+    pub fn camera_opened(&self) -> bool {
+        self.camera_opened.load(Ordering::Relaxed)
+    }
+
+    pub fn esp_connected(&self) -> bool {
+        self.esp_connected.load(Ordering::Relaxed)
+    }
+
+    /// Some ESP32 has passed the firmware handshake since startup.
+    pub fn esp_identified(&self) -> bool {
+        !self.esp_firmware.lock().unwrap().is_empty()
+    }
+
+    pub fn esp_ack_age(&self) -> Option<Duration> {
+        age(&self.last_esp_ack)
+    }
+
+    pub fn set_backend_connected(&self, connected: bool) {
+        self.backend_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn set_camera_opened(&self, opened: bool) {
+        self.camera_opened.store(opened, Ordering::Relaxed);
+    }
+
+    pub fn set_esp_connected(&self, connected: bool) {
+        self.esp_connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn set_esp_firmware(&self, controller: &str, firmware: FirmwareInfo) {
+        self.esp_firmware.lock().unwrap().insert(controller.to_string(), firmware);
+    }
+
+    pub fn mark_frame(&self) {
+        *self.last_frame.lock().unwrap() = Some(Instant::now());
+    }
+
+    pub fn mark_esp_ack(&self) {
+        *self.last_esp_ack.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Build the readiness report. The backend link is always critical; the
+    /// camera only while a session is active, since it is closed otherwise.
+    pub fn report(&self, session: &SessionState) -> HealthReport {
+        let backend_connected = self.backend_connected.load(Ordering::Relaxed);
+        let camera_opened = self.camera_opened.load(Ordering::Relaxed);
+        let last_frame_age = age(&self.last_frame);
+        let camera_ok = !session.connected
+            || (camera_opened && last_frame_age.is_some_and(|a| a < CAMERA_STALE_AFTER));
+
+        let ready = backend_connected && camera_ok;
+
+        HealthReport {
+            status: if ready { "ok" } else { "down" },
+            backend: BackendHealth { connected: backend_connected },
+            session: SessionHealth {
+                active: session.connected,
+                session_id: session.session_id,
+                microscope_id: session.microscope_id,
+            },
+            camera: CameraHealth {
+                ok: camera_ok,
+                opened: camera_opened,
+                last_frame_age_ms: last_frame_age.map(|a| a.as_millis() as u64),
+            },
+            esp32: EspHealth {
+                connected: self.esp_connected(),
+                last_ack_age_ms: age(&self.last_esp_ack).map(|a| a.as_millis() as u64),
+                firmware: self.esp_firmware.lock().unwrap().clone(),
+            },
+        }
+    }
+}
+
+fn age(at: &Mutex<Option<Instant>>) -> Option<Duration> {
+    at.lock().unwrap().map(|t| t.elapsed())
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub status: &'static str,
+    pub backend: BackendHealth,
+    pub session: SessionHealth,
+    pub camera: CameraHealth,
+    pub esp32: EspHealth,
+}
+
+impl HealthReport {
+    pub fn is_ready(&self) -> bool {
+        self.status == "ok"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BackendHealth {
+    pub connected: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionHealth {
+    pub active: bool,
+    pub session_id: Option<Uuid>,
+    pub microscope_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CameraHealth {
+    pub ok: bool,
+    pub opened: bool,
+    pub last_frame_age_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EspHealth {
+    pub connected: bool,
+    pub last_ack_age_ms: Option<u64>,
+    /// Last firmware seen in a handshake, by controller
+    pub firmware: BTreeMap<String, FirmwareInfo>,
+}
@@ -0,0 +1,144 @@
+use prometheus::{
+    exponential_buckets, Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+
+/// Process-wide metrics, scraped from `/metrics` by the monitoring server.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+
+    // Camera
+    pub camera_frames: IntCounter,
+    pub camera_read_failures: IntCounter,
+    pub camera_fps: Gauge,
+    pub jpeg_encode_seconds: Histogram,
+
+    // Streaming to the backend
+    pub frame_send_seconds: Histogram,
+    pub frame_bytes: Histogram,
+    pub frames_sent: IntCounter,
+    pub frames_dropped: IntCounterVec,
+
+    // ESP32 link
+    pub esp_replies: IntCounterVec,
+    pub esp_retries: IntCounter,
+    pub esp_timeouts: IntCounter,
+    pub esp_failures: IntCounter,
+    pub esp_frame_errors: IntCounter,
+    pub esp_connected: IntGaugeVec,
+    pub esp_reconnects: IntCounter,
+    pub esp_rtt_seconds: GaugeVec,
+    pub esp_resets: IntCounter,
+    pub serial_capture_dropped: IntCounter,
+    pub emergency_stops: IntCounter,
+
+    // Backend connection and command queue
+    pub ws_connected: IntGauge,
+    pub command_queue_depth: IntGauge,
+    pub commands: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("bsmanager".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let m = Self {
+            camera_frames: IntCounter::new("camera_frames_total", "Frames captured and encoded by the camera task").unwrap(),
+            camera_read_failures: IntCounter::new("camera_read_failures_total", "Camera reads that returned no frame").unwrap(),
+            camera_fps: Gauge::new("camera_capture_fps", "Camera capture rate over the last second").unwrap(),
+            jpeg_encode_seconds: Histogram::with_opts(
+                HistogramOpts::new("jpeg_encode_seconds", "Time to JPEG-encode one frame")
+                    .buckets(exponential_buckets(0.001, 2.0, 10).unwrap()),
+            ).unwrap(),
+
+            frame_send_seconds: Histogram::with_opts(
+                HistogramOpts::new("stream_frame_send_seconds", "Time to encode and write one stream frame to the backend")
+                    .buckets(exponential_buckets(0.001, 2.0, 12).unwrap()),
+            ).unwrap(),
+            frame_bytes: Histogram::with_opts(
+                HistogramOpts::new("stream_frame_bytes", "Size of base64 stream frames sent to the backend")
+                    .buckets(exponential_buckets(8_192.0, 2.0, 8).unwrap()),
+            ).unwrap(),
+            frames_sent: IntCounter::new("stream_frames_sent_total", "Stream frames sent to the backend").unwrap(),
+            frames_dropped: IntCounterVec::new(
+                Opts::new("stream_frames_dropped_total", "Stream ticks that did not send a frame"),
+                &["reason"],
+            ).unwrap(),
+
+            esp_replies: IntCounterVec::new(
+                Opts::new("esp_replies_total", "Replies received from the ESP32"),
+                &["reply"],
+            ).unwrap(),
+            esp_retries: IntCounter::new("esp_retries_total", "ESP32 message retries").unwrap(),
+            esp_timeouts: IntCounter::new("esp_timeouts_total", "ESP32 replies that timed out").unwrap(),
+            esp_failures: IntCounter::new("esp_failures_total", "ESP32 messages that failed after all retries").unwrap(),
+            esp_frame_errors: IntCounter::new("esp_frame_errors_total", "ESP32 frames discarded as corrupted or malformed").unwrap(),
+            esp_connected: IntGaugeVec::new(
+                Opts::new("esp_connected", "1 while the ESP32 serial port is open"),
+                &["controller"],
+            ).unwrap(),
+            esp_reconnects: IntCounter::new("esp_reconnects_total", "ESP32 serial port reopened after a disconnect").unwrap(),
+            esp_rtt_seconds: GaugeVec::new(
+                Opts::new("esp_rtt_seconds", "Round trip of the last answered ESP32 ping"),
+                &["controller"],
+            ).unwrap(),
+            esp_resets: IntCounter::new("esp_resets_total", "ESP32 boards reset after the link was lost").unwrap(),
+            serial_capture_dropped: IntCounter::new(
+                "serial_capture_dropped_total",
+                "Serial capture records dropped because the capture file fell behind",
+            ).unwrap(),
+            emergency_stops: IntCounter::new("emergency_stops_total", "Emergency stops sent to the ESP32").unwrap(),
+
+            ws_connected: IntGauge::new("websocket_connected", "1 while the backend WebSocket is connected").unwrap(),
+            command_queue_depth: IntGauge::new("command_queue_depth", "Commands waiting in the processor queue").unwrap(),
+            commands: IntCounterVec::new(
+                Opts::new("commands_total", "Backend commands handled by the processor"),
+                &["command"],
+            ).unwrap(),
+
+            registry,
+        };
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(m.camera_frames.clone()),
+            Box::new(m.camera_read_failures.clone()),
+            Box::new(m.camera_fps.clone()),
+            Box::new(m.jpeg_encode_seconds.clone()),
+            Box::new(m.frame_send_seconds.clone()),
+            Box::new(m.frame_bytes.clone()),
+            Box::new(m.frames_sent.clone()),
+            Box::new(m.frames_dropped.clone()),
+            Box::new(m.esp_replies.clone()),
+            Box::new(m.esp_retries.clone()),
+            Box::new(m.esp_timeouts.clone()),
+            Box::new(m.esp_failures.clone()),
+            Box::new(m.esp_frame_errors.clone()),
+            Box::new(m.esp_connected.clone()),
+            Box::new(m.esp_reconnects.clone()),
+            Box::new(m.esp_rtt_seconds.clone()),
+            Box::new(m.esp_resets.clone()),
+            Box::new(m.serial_capture_dropped.clone()),
+            Box::new(m.emergency_stops.clone()),
+            Box::new(m.ws_connected.clone()),
+            Box::new(m.command_queue_depth.clone()),
+            Box::new(m.commands.clone()),
+        ];
+        for c in collectors {
+            m.registry.register(c).expect("metric registered once");
+        }
+        m
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            tracing::error!(error = %e, "failed to encode metrics");
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
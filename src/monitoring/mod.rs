@@ -0,0 +1,7 @@
+pub mod health;
+pub mod metrics;
+pub mod server;
+pub mod systemd;
+
+pub use health::HEALTH;
+pub use metrics::METRICS;
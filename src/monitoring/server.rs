@@ -0,0 +1,122 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use tokio::time::{timeout, Duration};
+use tracing::{debug, info, warn};
+
+use crate::backend::session_state::SessionState;
+use crate::monitoring::health::HEALTH;
+use crate::monitoring::metrics::METRICS;
+
+/// Largest request head we are willing to buffer; scrapers send a few hundred bytes.
+const MAX_REQUEST: usize = 4096;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct HttpResponse {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl HttpResponse {
+    fn new(status: u16, content_type: &'static str, body: impl Into<String>) -> Self {
+        Self { status, content_type, body: body.into() }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            503 => "Service Unavailable",
+            _ => "Error",
+        }
+    }
+}
+
+/// Serve the local monitoring endpoints until the process exits.
+pub async fn serve(addr: SocketAddr, session_state: Arc<RwLock<SessionState>>) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "monitoring server listening");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(error = %e, "monitoring accept failed");
+                continue;
+            }
+        };
+        let session_state = Arc::clone(&session_state);
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, session_state).await {
+                debug!(%peer, error = %e, "monitoring request failed");
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, session_state: Arc<RwLock<SessionState>>) -> anyhow::Result<()> {
+    let head = timeout(READ_TIMEOUT, read_head(&mut stream)).await??;
+
+    // "GET /metrics HTTP/1.1"
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let response = if method != "GET" {
+        HttpResponse::new(405, "text/plain", "method not allowed\n")
+    } else {
+        route(path, &session_state).await
+    };
+
+    let header = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn route(path: &str, session_state: &RwLock<SessionState>) -> HttpResponse {
+    match path {
+        "/metrics" => HttpResponse::new(200, "text/plain; version=0.0.4", METRICS.render()),
+        // Liveness: the runtime is up and answering requests
+        "/healthz" => HttpResponse::new(200, "text/plain", "ok\n"),
+        // Readiness: every critical subsystem is working
+        "/readyz" => {
+            let report = HEALTH.report(&*session_state.read().await);
+            let status = if report.is_ready() { 200 } else { 503 };
+            let body = serde_json::to_string_pretty(&report).unwrap_or_default();
+            HttpResponse::new(status, "application/json", body + "\n")
+        }
+        _ => HttpResponse::new(404, "text/plain", "not found\n"),
+    }
+}
+
+async fn read_head(stream: &mut TcpStream) -> anyhow::Result<String> {
+    let mut buf = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if buf.windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+        if buf.len() > MAX_REQUEST {
+            anyhow::bail!("request head too large");
+        }
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
@@ -0,0 +1,107 @@
+use sd_notify::NotifyState;
+use tokio::task;
+use tokio::time::{self, Duration};
+use tracing::{debug, info, warn};
+
+use crate::controllers::camera::Camera;
+use crate::monitoring::{HEALTH, METRICS};
+
+/// Used for readiness/status updates when systemd has no watchdog configured.
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Longest wait on the camera probe per tick, well within the watchdog interval.
+const CAMERA_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn notify(state: &[NotifyState]) {
+    // No-op when NOTIFY_SOCKET is unset, i.e. not running under systemd
+    if let Err(e) = sd_notify::notify(false, state) {
+        debug!(error = %e, "sd_notify failed");
+    }
+}
+
+/// `WatchdogSec=` from the unit, if set.
+fn watchdog_timeout() -> Option<Duration> {
+    let mut usec = 0;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+}
+
+/// Supervise the daemon: send `READY=1` once the backend, camera and serial
+/// port are up, then ping the systemd watchdog only while the listener,
+/// processor and camera loops keep making progress.
+///
+/// Serial counts as up from the first ESP32 that passes the firmware
+/// handshake. Later unplugs do not withdraw readiness, as the processor
+/// reopens the board; they only show in the status line.
+pub async fn supervise(camera_index: i32) -> ! {
+    let watchdog = watchdog_timeout();
+    let interval = watchdog.map_or(DEFAULT_INTERVAL, |t| t / 2);
+    // A loop counts as stalled when it has not ticked within one full timeout
+    let stall_after = watchdog.unwrap_or(DEFAULT_INTERVAL * 2);
+    if let Some(timeout) = watchdog {
+        info!(?timeout, "systemd watchdog enabled");
+    }
+
+    let mut ticker = time::interval(interval);
+    let mut ready = false;
+    let mut camera_available = false;
+    let mut camera_probe: Option<task::JoinHandle<bool>> = None;
+
+    loop {
+        ticker.tick().await;
+
+        // The capture task only opens the camera during a session, so check
+        // the device directly until it has been seen once. Opening it blocks,
+        // so the probe runs on the blocking pool; one stuck opening the device
+        // is waited on again next tick rather than started twice.
+        camera_available = camera_available || HEALTH.camera_opened();
+        if !camera_available {
+            let probe = camera_probe.get_or_insert_with(|| task::spawn_blocking(move || Camera::is_available(camera_index)));
+            if let Ok(result) = time::timeout(CAMERA_PROBE_TIMEOUT, probe).await {
+                camera_probe = None;
+                camera_available = result.unwrap_or(false);
+            }
+        }
+
+        if !ready && HEALTH.backend_connected() && camera_available && HEALTH.esp_identified() {
+            info!("backend, camera and serial up, notifying systemd");
+            notify(&[NotifyState::Ready]);
+            ready = true;
+        }
+
+        let stalled = HEALTH.stalled_tasks(stall_after);
+        if watchdog.is_some() {
+            if stalled.is_empty() {
+                notify(&[NotifyState::Watchdog]);
+            } else {
+                warn!(?stalled, "loops not making progress, withholding watchdog ping");
+            }
+        }
+
+        notify(&[NotifyState::Status(&status_line(ready, camera_available, &stalled))]);
+    }
+}
+
+fn status_line(ready: bool, camera_available: bool, stalled: &[&str]) -> String {
+    let mut parts = Vec::new();
+    parts.push(if ready { "ready".to_string() } else { "starting".to_string() });
+    parts.push(format!(
+        "backend {}",
+        if HEALTH.backend_connected() { "connected" } else { "disconnected" }
+    ));
+    parts.push(if HEALTH.camera_opened() {
+        format!("camera {:.1} fps", METRICS.camera_fps.get())
+    } else if camera_available {
+        "camera idle".to_string()
+    } else {
+        "camera unavailable".to_string()
+    });
+    if !HEALTH.esp_connected() {
+        parts.push("esp32 offline".to_string());
+    } else if let Some(age) = HEALTH.esp_ack_age() {
+        parts.push(format!("esp32 ack {}s ago", age.as_secs()));
+    }
+    if !stalled.is_empty() {
+        parts.push(format!("stalled: {}", stalled.join(",")));
+    }
+    parts.join(", ")
+}
@@ -0,0 +1,109 @@
+use crate::backend::models::{Axis, Sense};
+
+/// How lost motion in the lead screws is compensated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklashMode {
+    /// Add the axis' take-up steps to the first move after a reversal
+    TakeUp,
+    /// Always finish a move travelling in this sense: moves the other way
+    /// overshoot the target and come back, so the slack is never in play
+    Approach(Sense),
+}
+
+/// Per-axis backlash in motor steps.
+#[derive(Debug, Clone)]
+pub struct Backlash {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+    pub mode: BacklashMode,
+}
+
+impl Backlash {
+    pub fn steps(&self, axis: Axis) -> u32 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        }
+    }
+
+    /// Extra steps needed before the stage follows the motor in `sense`.
+    pub fn take_up(&self, axis: Axis, last: Option<Sense>, sense: Sense) -> u32 {
+        if last.is_some_and(|l| l != sense) { self.steps(axis) } else { 0 }
+    }
+
+    /// Motor moves that carry the stage `steps` in `sense`, given the sense
+    /// the axis last moved in (`None` when unknown, e.g. after boot).
+    ///
+    /// The stage itself still moves exactly `steps`; only the ESP32 sees the extra.
+    /// An approach overshoot carries the stage one backlash past the target, so
+    /// without `room_to_overshoot` (e.g. at a soft limit) the move only takes up the slack.
+    pub fn plan(&self, axis: Axis, last: Option<Sense>, sense: Sense, steps: u32, room_to_overshoot: bool) -> Vec<(Sense, u32)> {
+        let backlash = self.steps(axis);
+        let take_up = self.take_up(axis, last, sense);
+        match self.mode {
+            BacklashMode::Approach(side) if side != sense && backlash > 0 && room_to_overshoot => {
+                // Overshoot by the backlash, then reverse: the return trip spends
+                // one backlash on slack and one moving back onto the target
+                vec![(sense, steps + take_up + backlash), (side, 2 * backlash)]
+            }
+            _ => vec![(sense, steps + take_up)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backlash(mode: BacklashMode) -> Backlash {
+        Backlash { x: 10, y: 0, z: 4, mode }
+    }
+
+    #[test]
+    fn take_up_only_after_a_known_reversal() {
+        let b = backlash(BacklashMode::TakeUp);
+        assert_eq!(b.take_up(Axis::X, None, Sense::Forward), 0);
+        assert_eq!(b.take_up(Axis::X, Some(Sense::Forward), Sense::Forward), 0);
+        assert_eq!(b.take_up(Axis::X, Some(Sense::Backward), Sense::Forward), 10);
+        assert_eq!(b.take_up(Axis::Y, Some(Sense::Backward), Sense::Forward), 0);
+    }
+
+    #[test]
+    fn take_up_mode_extends_the_first_move_after_a_reversal() {
+        let b = backlash(BacklashMode::TakeUp);
+        assert_eq!(b.plan(Axis::X, Some(Sense::Forward), Sense::Forward, 100, true), vec![(Sense::Forward, 100)]);
+        assert_eq!(b.plan(Axis::X, Some(Sense::Backward), Sense::Forward, 100, true), vec![(Sense::Forward, 110)]);
+        assert_eq!(b.plan(Axis::X, None, Sense::Backward, 100, true), vec![(Sense::Backward, 100)]);
+    }
+
+    #[test]
+    fn approach_mode_overshoots_moves_against_the_approach_side() {
+        let b = backlash(BacklashMode::Approach(Sense::Forward));
+        // Already approaching from the right side: nothing extra
+        assert_eq!(b.plan(Axis::X, Some(Sense::Forward), Sense::Forward, 100, true), vec![(Sense::Forward, 100)]);
+        // Reversing onto the wrong side: take up, overshoot, then come back
+        assert_eq!(
+            b.plan(Axis::X, Some(Sense::Forward), Sense::Backward, 100, true),
+            vec![(Sense::Backward, 120), (Sense::Forward, 20)]
+        );
+        assert_eq!(
+            b.plan(Axis::Z, Some(Sense::Backward), Sense::Backward, 50, true),
+            vec![(Sense::Backward, 54), (Sense::Forward, 8)]
+        );
+    }
+
+    #[test]
+    fn approach_mode_without_room_only_takes_up_the_slack() {
+        let b = backlash(BacklashMode::Approach(Sense::Forward));
+        assert_eq!(b.plan(Axis::X, Some(Sense::Forward), Sense::Backward, 100, false), vec![(Sense::Backward, 110)]);
+        assert_eq!(b.plan(Axis::X, None, Sense::Backward, 100, false), vec![(Sense::Backward, 100)]);
+    }
+
+    #[test]
+    fn approach_mode_without_backlash_moves_directly() {
+        let b = backlash(BacklashMode::Approach(Sense::Forward));
+        assert_eq!(b.plan(Axis::Y, Some(Sense::Forward), Sense::Backward, 100, true), vec![(Sense::Backward, 100)]);
+    }
+}
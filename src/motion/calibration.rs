@@ -0,0 +1,139 @@
+use crate::backend::models::{Axis, Sense};
+
+/// How one logical axis maps onto the hardware.
+#[derive(Debug, Clone, Copy)]
+pub struct AxisCalibration {
+    /// Index of the ESP32 controller the motor is wired to
+    pub controller: usize,
+    /// Motor index on that controller
+    pub motor: u8,
+    /// Motor steps per micrometre of travel
+    pub steps_per_um: f64,
+    /// Motor is wired or mounted backwards: swap FWD/BWD on the wire
+    pub invert: bool,
+    /// Default speed set on the motor with SET at connect, steps per second
+    pub speed: Option<u32>,
+    /// Default acceleration set on the motor with SET at connect, steps per second squared
+    pub accel: Option<u32>,
+}
+
+/// Per-axis calibration, so swapping a motor or lead screw is a config change.
+/// Also says which controllers the peripherals are wired to.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub x: AxisCalibration,
+    pub y: AxisCalibration,
+    pub z: AxisCalibration,
+    /// Controller wired to the front panel buttons
+    pub buttons: usize,
+    /// Controller reading the sensors
+    pub sensors: usize,
+}
+
+impl Calibration {
+    pub fn axis(&self, axis: Axis) -> &AxisCalibration {
+        match axis {
+            Axis::X => &self.x,
+            Axis::Y => &self.y,
+            Axis::Z => &self.z,
+        }
+    }
+
+    pub fn motor(&self, axis: Axis) -> u8 {
+        self.axis(axis).motor
+    }
+
+    pub fn controller(&self, axis: Axis) -> usize {
+        self.axis(axis).controller
+    }
+
+    /// Controller and motor index driving an axis.
+    pub fn route(&self, axis: Axis) -> (usize, u8) {
+        (self.controller(axis), self.motor(axis))
+    }
+
+    /// Logical axis driven by a motor on a controller.
+    pub fn axis_for_motor(&self, controller: usize, motor: u8) -> Option<Axis> {
+        Axis::ALL.into_iter().find(|&a| self.route(a) == (controller, motor))
+    }
+
+    /// Direction to send to the ESP32 for a logical move direction.
+    pub fn esp_sense(&self, axis: Axis, sense: Sense) -> Sense {
+        match (self.axis(axis).invert, sense) {
+            (false, s) => s,
+            (true, Sense::Forward) => Sense::Backward,
+            (true, Sense::Backward) => Sense::Forward,
+        }
+    }
+
+    /// Nearest whole number of steps for a distance.
+    pub fn um_to_steps(&self, axis: Axis, um: f64) -> i64 {
+        (um * self.axis(axis).steps_per_um).round() as i64
+    }
+
+    pub fn steps_to_um(&self, axis: Axis, steps: i64) -> f64 {
+        steps as f64 / self.axis(axis).steps_per_um
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axis(controller: usize, motor: u8, invert: bool) -> AxisCalibration {
+        AxisCalibration { controller, motor, steps_per_um: 2.5, invert, speed: None, accel: None }
+    }
+
+    fn calibration() -> Calibration {
+        Calibration { x: axis(0, 2, false), y: axis(0, 1, true), z: axis(1, 2, false), buttons: 0, sensors: 1 }
+    }
+
+    #[test]
+    fn routes_each_axis_to_its_controller_and_motor() {
+        let cal = calibration();
+        assert_eq!(cal.route(Axis::X), (0, 2));
+        assert_eq!(cal.route(Axis::Y), (0, 1));
+        assert_eq!(cal.route(Axis::Z), (1, 2));
+    }
+
+    #[test]
+    fn motors_map_back_to_their_axis() {
+        let cal = calibration();
+        for axis in Axis::ALL {
+            let (controller, motor) = cal.route(axis);
+            assert_eq!(cal.axis_for_motor(controller, motor), Some(axis));
+        }
+        // Same motor index on another board, and motors no axis uses
+        assert_eq!(cal.axis_for_motor(1, 1), None);
+        assert_eq!(cal.axis_for_motor(0, 3), None);
+        assert_eq!(cal.axis_for_motor(2, 2), None);
+    }
+
+    #[test]
+    fn esp_sense_swaps_only_inverted_axes() {
+        let cal = calibration();
+        assert_eq!(cal.esp_sense(Axis::X, Sense::Forward), Sense::Forward);
+        assert_eq!(cal.esp_sense(Axis::X, Sense::Backward), Sense::Backward);
+        assert_eq!(cal.esp_sense(Axis::Y, Sense::Forward), Sense::Backward);
+        assert_eq!(cal.esp_sense(Axis::Y, Sense::Backward), Sense::Forward);
+    }
+
+    #[test]
+    fn esp_sense_is_its_own_inverse() {
+        let cal = calibration();
+        for axis in Axis::ALL {
+            for sense in [Sense::Forward, Sense::Backward] {
+                assert_eq!(cal.esp_sense(axis, cal.esp_sense(axis, sense)), sense);
+            }
+        }
+    }
+
+    #[test]
+    fn distances_round_to_whole_steps() {
+        let cal = calibration();
+        assert_eq!(cal.um_to_steps(Axis::X, 10.0), 25);
+        assert_eq!(cal.um_to_steps(Axis::X, 0.3), 1);
+        assert_eq!(cal.um_to_steps(Axis::X, -4.0), -10);
+        assert_eq!(cal.steps_to_um(Axis::X, 25), 10.0);
+    }
+}
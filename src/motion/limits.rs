@@ -0,0 +1,159 @@
+use crate::backend::models::{Axis, Sense};
+
+/// Allowed travel on one axis in steps; `None` means unbounded on that side.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxisLimits {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+/// What to do with a relative move that would cross a soft limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitMode {
+    /// Move as far as the limit allows
+    Clamp,
+    /// Refuse the whole move
+    Reject,
+}
+
+/// Software travel limits, checked before any move is sent to the ESP32.
+#[derive(Debug, Clone)]
+pub struct SoftLimits {
+    pub x: AxisLimits,
+    pub y: AxisLimits,
+    pub z: AxisLimits,
+    /// Highest Z (towards the slide) before the objective risks touching it.
+    /// Tighter than `z.max`, which only protects the mechanics.
+    pub z_objective_max: Option<i64>,
+    pub mode: LimitMode,
+}
+
+/// Outcome of checking a relative move.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimitCheck {
+    Allowed,
+    /// Only `steps` of the move fit; `reason` explains which limit applies
+    Clamped { steps: u32, reason: String },
+    Rejected(String),
+}
+
+impl SoftLimits {
+    /// Effective `(min, max)` for an axis and a label for the max side.
+    fn bounds(&self, axis: Axis) -> (Option<i64>, Option<i64>, &'static str) {
+        let limits = match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+            Axis::Z => self.z,
+        };
+        match (axis, limits.max, self.z_objective_max) {
+            (Axis::Z, Some(max), Some(obj)) if obj < max => (limits.min, Some(obj), "objective safety limit"),
+            (Axis::Z, None, Some(obj)) => (limits.min, Some(obj), "objective safety limit"),
+            _ => (limits.min, limits.max, "soft limit"),
+        }
+    }
+
+    /// Check moving `steps` in `sense` from `from`.
+    pub fn check_move(&self, axis: Axis, from: i64, sense: Sense, steps: u32) -> LimitCheck {
+        let (min, max, max_label) = self.bounds(axis);
+        let (room, bound, label) = match sense {
+            Sense::Forward => (max.map(|m| m - from), max, max_label),
+            Sense::Backward => (min.map(|m| from - m), min, "soft limit"),
+        };
+        let (Some(room), Some(bound)) = (room, bound) else {
+            return LimitCheck::Allowed;
+        };
+        if room >= steps as i64 {
+            return LimitCheck::Allowed;
+        }
+
+        let room = room.max(0) as u32;
+        let reason = format!(
+            "{} axis {} is {} (at {}, requested {} steps, {} available)",
+            axis, label, bound, from, steps, room
+        );
+        match self.mode {
+            LimitMode::Clamp if room > 0 => LimitCheck::Clamped { steps: room, reason },
+            _ => LimitCheck::Rejected(reason),
+        }
+    }
+
+    /// Check an absolute target; targets outside the limits are always rejected.
+    pub fn check_target(&self, axis: Axis, target: i64) -> Result<(), String> {
+        let (min, max, max_label) = self.bounds(axis);
+        if let Some(min) = min.filter(|&m| target < m) {
+            return Err(format!("{} axis target {} is below soft limit {}", axis, target, min));
+        }
+        if let Some(max) = max.filter(|&m| target > m) {
+            return Err(format!("{} axis target {} is above {} {}", axis, target, max_label, max));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(mode: LimitMode) -> SoftLimits {
+        let bounded = AxisLimits { min: Some(0), max: Some(1000) };
+        SoftLimits { x: bounded, y: AxisLimits::default(), z: bounded, z_objective_max: Some(800), mode }
+    }
+
+    #[test]
+    fn move_within_limits_is_allowed() {
+        let limits = limits(LimitMode::Clamp);
+        assert_eq!(limits.check_move(Axis::X, 500, Sense::Forward, 500), LimitCheck::Allowed);
+        assert_eq!(limits.check_move(Axis::X, 500, Sense::Backward, 500), LimitCheck::Allowed);
+        assert_eq!(limits.check_move(Axis::Y, 0, Sense::Backward, u32::MAX), LimitCheck::Allowed);
+    }
+
+    #[test]
+    fn clamp_mode_shortens_the_move_to_the_limit() {
+        let limits = limits(LimitMode::Clamp);
+        assert!(matches!(
+            limits.check_move(Axis::X, 900, Sense::Forward, 300),
+            LimitCheck::Clamped { steps: 100, .. }
+        ));
+        assert!(matches!(
+            limits.check_move(Axis::X, 100, Sense::Backward, 300),
+            LimitCheck::Clamped { steps: 100, .. }
+        ));
+    }
+
+    #[test]
+    fn clamp_mode_rejects_when_no_room_is_left() {
+        let limits = limits(LimitMode::Clamp);
+        assert!(matches!(limits.check_move(Axis::X, 1000, Sense::Forward, 1), LimitCheck::Rejected(_)));
+        // Already past the limit, e.g. after the limits were tightened
+        assert!(matches!(limits.check_move(Axis::X, -5, Sense::Backward, 1), LimitCheck::Rejected(_)));
+    }
+
+    #[test]
+    fn reject_mode_refuses_the_whole_move() {
+        let limits = limits(LimitMode::Reject);
+        assert!(matches!(limits.check_move(Axis::X, 900, Sense::Forward, 300), LimitCheck::Rejected(_)));
+    }
+
+    #[test]
+    fn objective_limit_is_tighter_than_z_max() {
+        let limits = limits(LimitMode::Clamp);
+        match limits.check_move(Axis::Z, 700, Sense::Forward, 200) {
+            LimitCheck::Clamped { steps, reason } => {
+                assert_eq!(steps, 100);
+                assert!(reason.contains("objective"), "{}", reason);
+            }
+            other => panic!("expected a clamp, got {:?}", other),
+        }
+        assert!(limits.check_target(Axis::Z, 900).is_err());
+        assert!(limits.check_target(Axis::X, 900).is_ok());
+    }
+
+    #[test]
+    fn targets_outside_the_limits_are_rejected() {
+        let limits = limits(LimitMode::Clamp);
+        assert!(limits.check_target(Axis::X, -1).is_err());
+        assert!(limits.check_target(Axis::X, 1001).is_err());
+        assert!(limits.check_target(Axis::X, 0).is_ok());
+        assert!(limits.check_target(Axis::Y, i64::MIN).is_ok());
+    }
+}
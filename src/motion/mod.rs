@@ -0,0 +1,9 @@
+pub mod backlash;
+pub mod calibration;
+pub mod limits;
+pub mod stage;
+pub mod velocity;
+
+pub use limits::LimitCheck;
+pub use stage::Stage;
+pub use velocity::VelocityDrive;
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+use crate::backend::models::{Axis, Position, Sense};
+
+/// Absolute stage position in motor steps, persisted so it survives restarts.
+///
+/// Only moves the ESP32 acknowledged are applied; forward moves count up.
+/// Homing is deliberately not persisted: the stage may have been moved by
+/// hand while powered off, so every axis must be homed again after boot.
+pub struct Stage {
+    position: Position,
+    homed: [bool; 3],
+    path: PathBuf,
+}
+
+impl Stage {
+    /// Load the last saved position, starting from zero if there is none.
+    pub fn load(path: &Path) -> Self {
+        let position = match std::fs::read_to_string(path) {
+            Ok(text) => match serde_json::from_str(&text) {
+                Ok(position) => {
+                    info!(path = %path.display(), ?position, "restored stage position");
+                    position
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "corrupt stage state, starting at zero");
+                    Position::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Position::default(),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "cannot read stage state, starting at zero");
+                Position::default()
+            }
+        };
+        Self { position, homed: [false; 3], path: path.to_path_buf() }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn is_homed(&self, axis: Axis) -> bool {
+        self.homed[axis as usize]
+    }
+
+    pub fn homed_axes(&self) -> Vec<Axis> {
+        Axis::ALL.into_iter().filter(|&a| self.is_homed(a)).collect()
+    }
+
+    /// The axis reached its endstop: this is now zero.
+    pub fn set_home(&mut self, axis: Axis) {
+        self.homed[axis as usize] = true;
+        self.position.set(axis, 0);
+        self.save();
+    }
+
+    /// The tracked position can no longer be trusted (e.g. a limit switch cut a move short).
+    pub fn invalidate(&mut self, axis: Axis) {
+        self.homed[axis as usize] = false;
+    }
+
+    /// Record a completed relative move.
+    pub fn apply_move(&mut self, axis: Axis, sense: Sense, steps: u32) {
+        let delta = match sense {
+            Sense::Forward => steps as i64,
+            Sense::Backward => -(steps as i64),
+        };
+        self.position.set(axis, self.position.get(axis) + delta);
+        self.save();
+    }
+
+    fn save(&self) {
+        // Write then rename so a power cut never leaves a half-written file
+        let tmp = self.path.with_extension("tmp");
+        let result = serde_json::to_vec(&self.position)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(&tmp, json))
+            .and_then(|()| std::fs::rename(&tmp, &self.path));
+        match result {
+            Ok(()) => debug!(position = ?self.position, "saved stage position"),
+            Err(e) => warn!(path = %self.path.display(), error = %e, "failed to save stage position"),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use tokio::time::{Duration, Instant};
+
+use crate::backend::models::Axis;
+
+/// Joystick-style motion from `SetVelocity`: per-axis speeds in steps per
+/// second (forward positive), ramped towards the requested vector.
+///
+/// Pure bookkeeping; the processor turns speed changes into ESP32 jogs.
+pub struct VelocityDrive {
+    target: [f64; 3],
+    current: [f64; 3],
+    /// Speed last sent to the ESP32, 0 while the motor is stopped
+    sent: [i64; 3],
+    /// Estimated steps travelled since the axis last started, from the ramp
+    travelled: [f64; 3],
+    last_update: Instant,
+    last_tick: Instant,
+    last_refresh: Instant,
+}
+
+impl VelocityDrive {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            target: [0.0; 3],
+            current: [0.0; 3],
+            sent: [0; 3],
+            travelled: [0.0; 3],
+            last_update: now,
+            last_tick: now,
+            last_refresh: now,
+        }
+    }
+
+    pub fn set_target(&mut self, target: [f64; 3], now: Instant) {
+        self.target = target;
+        self.last_update = now;
+    }
+
+    /// Move the current speeds towards the target by at most `accel` steps/s²,
+    /// aiming for zero once no update has arrived within `timeout`.
+    pub fn advance(&mut self, now: Instant, accel: f64, timeout: Duration) {
+        if now - self.last_update > timeout {
+            self.target = [0.0; 3];
+        }
+        let dt = (now - self.last_tick).as_secs_f64();
+        self.last_tick = now;
+        for i in 0..3 {
+            self.travelled[i] += self.current[i] * dt;
+            let max_change = accel * dt;
+            self.current[i] += (self.target[i] - self.current[i]).clamp(-max_change, max_change);
+        }
+    }
+
+    /// Speed to run the axis at now, in whole steps per second.
+    pub fn speed(&self, axis: Axis) -> i64 {
+        self.current[axis as usize].round() as i64
+    }
+
+    pub fn sent(&self, axis: Axis) -> i64 {
+        self.sent[axis as usize]
+    }
+
+    pub fn travelled(&self, axis: Axis) -> f64 {
+        self.travelled[axis as usize]
+    }
+
+    /// Record what the ESP32 is now running; starting or stopping the motor
+    /// restarts the travel estimate, as the stage position is updated on stop.
+    pub fn mark_sent(&mut self, axis: Axis, speed: i64) {
+        let i = axis as usize;
+        if self.sent[i] == 0 || speed == 0 {
+            self.travelled[i] = 0.0;
+        }
+        self.sent[i] = speed;
+    }
+
+    /// Bring the axis to rest immediately, e.g. at a limit.
+    pub fn hold(&mut self, axis: Axis) {
+        self.target[axis as usize] = 0.0;
+        self.current[axis as usize] = 0.0;
+    }
+
+    /// The ESP32 stopped the motor itself.
+    pub fn halt(&mut self, axis: Axis) {
+        self.hold(axis);
+        self.mark_sent(axis, 0);
+    }
+
+    /// True every `interval`, to keep the ESP32's dead-man timer from expiring.
+    pub fn refresh_due(&mut self, now: Instant, interval: Duration) -> bool {
+        if now - self.last_refresh < interval {
+            return false;
+        }
+        self.last_refresh = now;
+        true
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.target.iter().chain(&self.current).all(|&v| v == 0.0) && self.sent.iter().all(|&s| s == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCEL: f64 = 1000.0;
+    const TIMEOUT: Duration = Duration::from_millis(300);
+    const TICK: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn speed_ramps_towards_the_target_at_the_acceleration() {
+        let start = Instant::now();
+        let mut drive = VelocityDrive::new(start);
+        drive.set_target([500.0, -500.0, 0.0], start);
+        drive.advance(start + TICK, ACCEL, TIMEOUT);
+        assert_eq!(drive.speed(Axis::X), 50);
+        assert_eq!(drive.speed(Axis::Y), -50);
+        assert_eq!(drive.speed(Axis::Z), 0);
+
+        // Never past the target
+        let mut now = start + TICK;
+        for _ in 0..4 {
+            now += TICK;
+            drive.set_target([100.0, -500.0, 0.0], now);
+            drive.advance(now, ACCEL, TIMEOUT);
+        }
+        assert_eq!(drive.speed(Axis::X), 100);
+        assert_eq!(drive.speed(Axis::Y), -250);
+    }
+
+    #[test]
+    fn speed_decays_to_zero_without_updates() {
+        let start = Instant::now();
+        let mut drive = VelocityDrive::new(start);
+        drive.set_target([100.0, 0.0, 0.0], start);
+        let mut now = start;
+        for _ in 0..4 {
+            now += TICK;
+            drive.advance(now, ACCEL, TIMEOUT);
+        }
+        assert_eq!(drive.speed(Axis::X), 100);
+
+        // Past the timeout the target drops to zero and the ramp brings the axis down
+        now = start + TIMEOUT + TICK;
+        drive.advance(now, ACCEL, TIMEOUT);
+        for _ in 0..4 {
+            now += TICK;
+            drive.advance(now, ACCEL, TIMEOUT);
+        }
+        assert_eq!(drive.speed(Axis::X), 0);
+        assert!(drive.is_idle());
+    }
+
+    #[test]
+    fn travel_is_estimated_from_the_ramp_and_reset_on_start_and_stop() {
+        let start = Instant::now();
+        let mut drive = VelocityDrive::new(start);
+        drive.set_target([100.0, 0.0, 0.0], start);
+        drive.advance(start + TICK, ACCEL, TIMEOUT);
+        drive.mark_sent(Axis::X, 50);
+        drive.advance(start + TICK * 2, ACCEL, TIMEOUT);
+        assert!((drive.travelled(Axis::X) - 50.0 * 0.05).abs() < 1e-9);
+
+        // A speed change while running keeps the estimate; a stop resets it
+        drive.mark_sent(Axis::X, 100);
+        assert!(drive.travelled(Axis::X) > 0.0);
+        drive.mark_sent(Axis::X, 0);
+        assert_eq!(drive.travelled(Axis::X), 0.0);
+    }
+
+    #[test]
+    fn halt_stops_the_axis_at_once() {
+        let start = Instant::now();
+        let mut drive = VelocityDrive::new(start);
+        drive.set_target([0.0, 0.0, 200.0], start);
+        drive.advance(start + TICK, ACCEL, TIMEOUT);
+        drive.mark_sent(Axis::Z, drive.speed(Axis::Z));
+        assert!(!drive.is_idle());
+
+        drive.halt(Axis::Z);
+        assert_eq!(drive.speed(Axis::Z), 0);
+        assert_eq!(drive.sent(Axis::Z), 0);
+        assert!(drive.is_idle());
+    }
+
+    #[test]
+    fn refresh_is_due_once_per_interval() {
+        let start = Instant::now();
+        let mut drive = VelocityDrive::new(start);
+        assert!(!drive.refresh_due(start + TICK, TICK * 4));
+        assert!(drive.refresh_due(start + TICK * 4, TICK * 4));
+        assert!(!drive.refresh_due(start + TICK * 5, TICK * 4));
+        assert!(drive.refresh_due(start + TICK * 8, TICK * 4));
+    }
+}